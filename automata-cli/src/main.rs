@@ -1,34 +1,111 @@
 extern crate automata_lib;
 
+mod scan;
+
 use automata_lib::*;
-use rand::{thread_rng, Rng};
+use image::RgbImage;
+use num_bigint::RandBigInt;
+use rand::thread_rng;
+use std::fs::File;
+use std::io::{BufReader, BufWriter};
 use std::path::PathBuf;
+use std::process;
 use structopt::StructOpt;
 
 #[derive(StructOpt)]
 struct Opt {
-    /// Define the rule number of colors in the automata, 2,3 or 4 is supported
+    /// Define the rule number of colors in the automata, from 2 to 16 is supported
     #[structopt(
         short = "c",
         long = "colors",
         default_value = "3",
-        possible_values(&["2", "3", "4"])
+        possible_values(&["2", "3", "4", "5", "6", "7", "8", "9", "10", "11", "12", "13", "14", "15", "16"])
     )]
     colors: u8,
+    /// Define the radius of the rule neighborhood, the new cell value depends on the 2*radius+1 cells above it.
+    #[structopt(long = "radius", default_value = "1")]
+    radius: u8,
+    /// Define how the rule number is encoded, full, totalistic, outer-totalistic or symmetric-ordered.
+    /// When not provided, full is used for 2 colors, symmetric-ordered for 3 colors and totalistic above.
+    #[structopt(
+        short = "k",
+        long = "kind",
+        possible_values(&["full", "totalistic", "outer-totalistic", "symmetric-ordered"])
+    )]
+    kind: Option<RuleKind>,
     /// Define the rule number that the cellular automata will follow, represend as an integer
     /// If not provided, a random rule will be choosen.
     #[structopt(short = "r", long = "rule")]
-    rule: Option<u64>,
+    rule: Option<BigUint>,
+    /// When defined, the random rule is choosen among canonical rules only,
+    /// the rules with the smallest number of their class under mirror and color permutations.
+    /// With the scan subcommand, only canonical rules are classified.
+    #[structopt(long = "canonical", conflicts_with = "rule")]
+    canonical: bool,
     /// Define the number of step to iterate on the cellular automata
     #[structopt(short = "s", long = "steps", default_value = "40")]
     steps: u32,
     /// Define the width of the viewed area of the cellular automata
     #[structopt(short = "w", long = "width", default_value = "80")]
     view_width: u32,
+    /// Define the lattice boundary: infinite, periodic, reflective, fixed or fixed:<value>.
+    /// All but infinite are finite lattices of 'size' cells.
+    #[structopt(short = "b", long = "boundary", default_value = "infinite")]
+    boundary: Boundary,
+    /// Define the number of cells of a finite lattice, when not provided the width is used.
+    #[structopt(long = "size")]
+    size: Option<u32>,
+    /// Define the initial cells: seed, random[:<density>[:<seed>]], <cells> like 0120012,
+    /// defect:<background>:<cells> or file:<path> of a text or PNG file.
+    /// Cells are centered, in the 'size' cells of the lattice.
+    #[structopt(short = "i", long = "init", default_value = "seed")]
+    init: InitialCondition,
     /// Define the starting point of the viewed area, in relation to the starting black cell.
     /// When not provided, view will be centered around the starting cell
     #[structopt(short = "x", allow_hyphen_values(true))]
     view_start: Option<i32>,
+    /// When defined, steps are computed with HashLife1D, that can jump far ahead for regular patterns.
+    /// Only supported on an infinite lattice without subcommand, mostly useful with --last.
+    #[structopt(long = "hashlife")]
+    hashlife: bool,
+    /// When defined, the rule is second order: the next cells are the rule output minus the previous cells,
+    /// modulo the number of colors, an exclusive or with 2 colors. The first previous cells are of color 0.
+    /// The automata is then reversible. Only supported on a finite lattice without subcommand.
+    #[structopt(long = "second-order", conflicts_with_all = &["hashlife", "save-state", "resume"])]
+    second_order: bool,
+    /// Define the order of the updates of the cells in each step, each step being one row: synchronous,
+    /// random-sequential, sweep from left to right, alpha:<alpha> where each cell is updated with probability alpha,
+    /// or block:<size> for blocks of consecutive cells in turn. Only synchronous is supported on an infinite lattice,
+    /// with --hashlife and with --second-order.
+    #[structopt(long = "update", default_value = "synchronous")]
+    update: UpdateScheme,
+    /// When defined, the new cells of the rule are replaced with probability 'noise' by another color,
+    /// all equally likely, with 2 colors the new cells are flipped. Stochastic rules need a finite --boundary.
+    #[structopt(
+        long = "noise",
+        conflicts_with_all = &["hashlife", "second-order", "save-state", "resume"]
+    )]
+    noise: Option<f64>,
+    /// When defined, the rule is the Domany-Kinzel automata with probabilities p1,p2 instead of the rule options:
+    /// a 2 colors new cell is 1 with probability p1 when one of its 2 neighbors is 1, p2 when both are.
+    /// p1,p1 is directed site percolation and p1,p1*(2-p1) directed bond percolation.
+    #[structopt(
+        long = "domany-kinzel",
+        use_delimiter = true,
+        conflicts_with_all = &["noise", "rule", "hashlife", "second-order", "save-state", "resume"]
+    )]
+    domany_kinzel: Vec<f64>,
+    /// Define the seed of the random generator of the new cells of --noise and --domany-kinzel,
+    /// runs with the same seed are the same.
+    #[structopt(long = "rng-seed", default_value = "0")]
+    rng_seed: u64,
+    /// When defined, the automata state after the last step is saved in the named checkpoint file.
+    #[structopt(long = "save-state", parse(from_os_str), conflicts_with = "hashlife")]
+    save_state: Option<PathBuf>,
+    /// When defined, the automata is resumed from the named checkpoint file, for 'steps' more steps.
+    /// The rule, boundary, update scheme, cells and view come from the checkpoint.
+    #[structopt(long = "resume", parse(from_os_str), conflicts_with = "hashlife")]
+    resume: Option<PathBuf>,
     /// When defines, only print to stdout the last 'last' steps.
     /// When undefined, print all steps.
     #[structopt(long = "last")]
@@ -37,38 +114,667 @@ struct Opt {
     /// When undefined, output is displayed as text in stdout
     #[structopt(short = "o", long = "output", parse(from_os_str))]
     output: Option<PathBuf>,
+    #[structopt(subcommand)]
+    command: Option<Command>,
+}
+#[derive(StructOpt)]
+enum Command {
+    /// Print the transient, period and shift of the cycle reached on each lattice size from 1 to max-size.
+    /// Lattices are periodic, unless another finite boundary is given,
+    /// sizes that are too small for the initial cells are skipped.
+    Analyze {
+        /// Define the largest lattice size
+        #[structopt(long = "max-size", default_value = "16")]
+        max_size: u32,
+        /// Define the number of steps after which a lattice is reported without cycle
+        #[structopt(long = "max-steps", default_value = "100000")]
+        max_steps: u32,
+    },
+    /// Print measures of the run instead of the cells: the color densities and the active width
+    /// of each step, the entropy of the blocks of cells and of the history of each position,
+    /// and the spreading rate of the active region. Only the last 'last' steps are measured when defined.
+    Stats {
+        /// Define the output format, csv or json
+        #[structopt(long = "format", default_value = "csv", possible_values(&["csv", "json"]))]
+        format: scan::Format,
+        /// Define the largest block size of the block entropies
+        #[structopt(long = "max-block", default_value = "8")]
+        max_block: usize,
+    },
+    /// Print the cells with an 'x' where a copy of the automata, whose cell 'cell' was changed, differs.
+    /// When 'output' is defined, the differing cells are red in the PNG image.
+    /// The left and right Lyapunov exponents, the speeds of the difference fronts, are printed last.
+    /// When 'last' is defined, the cell is changed on the first of the last steps.
+    Damage {
+        /// Define the position of the changed cell
+        #[structopt(long = "cell", default_value = "0", allow_hyphen_values(true))]
+        cell: i32,
+    },
+    /// Print the properties of the global map of the rule from its de Bruijn graph:
+    /// whether it is surjective, whether it is injective, the rule being reversible,
+    /// and its shortest Garden-of-Eden pattern, a pattern of cells without preimage.
+    Info,
+    /// Print the backward tree of the cells of the last step: their preimages, the cells of the previous step
+    /// that evolve to them, then the preimages of each preimage, down to 'depth' steps back.
+    /// Each line shows the step, the path in the tree and the number of preimages of the cells,
+    /// only the first 'max-preimages' preimages of each cells are followed.
+    /// When 'output' is defined, the cells of the lines are the rows of the PNG image.
+    Preimages {
+        /// Define the number of steps back
+        #[structopt(long = "depth", default_value = "1")]
+        depth: u32,
+        /// Define the largest number of preimages followed for each cells
+        #[structopt(long = "max-preimages", default_value = "4")]
+        max_preimages: usize,
+    },
+    /// Measure each rule from 'from' to 'to', or each rule of 'rules', on all the cores.
+    /// The class metric classifies the rules in Wolfram classes 1 to 4 with a confidence score,
+    /// from a run of 'steps' steps of 'size' random cells on a periodic lattice, with the measures the classes
+    /// are based on.
+    /// The stats metric measures the spreading rate and the entropies of the run of 'steps' steps
+    /// of 'width' cells from 'init' with the 'update' scheme, the class metric always updates synchronously.
+    /// Only canonical rules are measured with --canonical.
+    /// Stochastic rules, --hashlife, --second-order and checkpoints are not supported.
+    Scan {
+        /// Define the first rule number, 0 when not provided
+        #[structopt(long = "from")]
+        from: Option<BigUint>,
+        /// Define the last rule number, the last rule of the kind when not provided
+        #[structopt(long = "to")]
+        to: Option<BigUint>,
+        /// Define the rule numbers to measure, separated by commas, instead of a range
+        #[structopt(long = "rules", use_delimiter = true, conflicts_with_all = &["from", "to"])]
+        rules: Vec<BigUint>,
+        /// Define the measures of each rule, class and stats, separated by commas
+        #[structopt(
+            long = "metrics",
+            default_value = "class",
+            use_delimiter = true,
+            possible_values(&["class", "stats"])
+        )]
+        metrics: Vec<scan::Metric>,
+        /// Define the report order, rule or one of the measures like class, confidence or spreading_rate.
+        /// Rules are in increasing order of the measure, from the most confident for confidence.
+        /// With --out-dir, the index is sorted once all the rules are measured.
+        #[structopt(long = "sort", default_value = "rule")]
+        sort: scan::ScanSort,
+        /// Define the output format, csv or json
+        #[structopt(long = "format", default_value = "csv", possible_values(&["csv", "json"]))]
+        format: scan::Format,
+        /// Define the seed of the random cells the rules are classified from
+        #[structopt(long = "seed", default_value = "0")]
+        seed: u64,
+        /// When defined, a PNG thumbnail of the run is saved for each rule in the named directory,
+        /// with an index of the measures, index.csv or index.jsonl with one JSON object per line.
+        /// The parameters of the scan are saved in scan.json, an interrupted scan resumes from the index
+        /// when run with the same parameters.
+        #[structopt(long = "out-dir", parse(from_os_str))]
+        out_dir: Option<PathBuf>,
+    },
 }
+// Automata operations needed to print or save the steps
+trait Render {
+    fn step(&mut self, n_step: u32) -> Result<()>;
+    // Skip steps that are not rendered
+    fn skip(&mut self, n_step: u32) -> Result<()> {
+        self.step(n_step)
+    }
+    fn as_text(&self) -> String;
+    fn as_image_buffer(&mut self, n_step: u32) -> Result<RgbImage>;
+}
+impl<R: CellularRule> Render for Automata1D<R> {
+    fn step(&mut self, n_step: u32) -> Result<()> {
+        Automata1D::step(self, n_step);
+        Ok(())
+    }
+    fn skip(&mut self, n_step: u32) -> Result<()> {
+        // Linear rules jump there directly, their jump can fail when the cells grow too far
+        let step = self.get_cur_step() + n_step;
+        self.jump_to(step)
+    }
+    fn as_text(&self) -> String {
+        Automata1D::as_text(self)
+    }
+    fn as_image_buffer(&mut self, n_step: u32) -> Result<RgbImage> {
+        Ok(Automata1D::as_image_buffer(self, n_step))
+    }
+}
+impl Render for SecondOrderAutomata1D {
+    fn step(&mut self, n_step: u32) -> Result<()> {
+        SecondOrderAutomata1D::step(self, n_step);
+        Ok(())
+    }
+    fn as_text(&self) -> String {
+        SecondOrderAutomata1D::as_text(self)
+    }
+    fn as_image_buffer(&mut self, n_step: u32) -> Result<RgbImage> {
+        Ok(SecondOrderAutomata1D::as_image_buffer(self, n_step))
+    }
+}
+impl Render for HashLife1D<Rule1D> {
+    fn step(&mut self, n_step: u32) -> Result<()> {
+        HashLife1D::step(self, n_step as u64)
+    }
+    fn as_text(&self) -> String {
+        HashLife1D::as_text(self)
+    }
+    fn as_image_buffer(&mut self, n_step: u32) -> Result<RgbImage> {
+        HashLife1D::as_image_buffer(self, n_step)
+    }
+}
+
 fn main() {
-    let mut rng = thread_rng();
     let opt = Opt::from_args();
-
-    let rule_nb = match opt.rule {
-        Some(v) => v,
-        _ => rng.gen_range(0, Rule1D::get_max_nb(opt.colors)),
+    if let Err(e) = run(opt) {
+        eprintln!("error: {}", e);
+        // Invalid parameters exit with 2, like command line parsing errors
+        let code = match e {
+            Error::Image(_) | Error::Io(_) => 1,
+            _ => 2,
+        };
+        process::exit(code);
+    }
+}
+fn run(opt: Opt) -> Result<()> {
+    let print_step = match opt.last {
+        Some(v) if v <= opt.steps => opt.steps - v,
+        _ => 0,
     };
-    let rule = Rule1D::new(opt.colors, rule_nb);
+    // HashLife1D and SecondOrderAutomata1D only print or save the steps, the subcommands use Automata1D
+    if opt.command.is_some() {
+        let engines = [
+            ("--hashlife", opt.hashlife),
+            ("--second-order", opt.second_order),
+        ];
+        if let Some((option, _used)) = engines.iter().find(|(_option, used)| *used) {
+            return Err(Error::IncompatibleOptions(format!(
+                "{} is not supported by the subcommands",
+                option
+            )));
+        }
+    }
+    if let Some(Command::Scan { .. }) = opt.command {
+        // Scans measure the deterministic rules, from the options rather than a checkpoint
+        let unsupported = [
+            ("--noise", opt.noise.is_some()),
+            ("--domany-kinzel", !opt.domany_kinzel.is_empty()),
+            ("--resume", opt.resume.is_some()),
+            ("--save-state", opt.save_state.is_some()),
+        ];
+        if let Some((option, _used)) = unsupported.iter().find(|(_option, used)| *used) {
+            return Err(Error::IncompatibleOptions(format!(
+                "{} is not supported by scan",
+                option
+            )));
+        }
+    }
+    if let Some(path) = &opt.resume {
+        let mut automata: Automata1D =
+            Automata1D::load_checkpoint(BufReader::new(File::open(path)?))?;
+        // Steps are counted from the checkpoint step
+        let start = automata.get_cur_step();
+        render(&mut automata, print_step, opt.steps, opt.output)?;
+        if let Some(path) = &opt.save_state {
+            automata.save_checkpoint(BufWriter::new(File::create(path)?))?;
+        }
+        let rule = automata.get_rule();
+        println!(
+            "Colors: {}, Radius: {}, Kind: {}, Rule: {}{}, Steps: {} to {}",
+            rule.get_n_colors(),
+            rule.get_radius(),
+            rule.get_kind(),
+            rule.get_rule_nb(),
+            update_info(automata.get_update_scheme()),
+            start,
+            automata.get_cur_step()
+        );
+        return Ok(());
+    }
+    let mut rng = thread_rng();
+
+    let kind = opt
+        .kind
+        .unwrap_or_else(|| RuleKind::default_for(opt.colors));
+    let size = opt.size.unwrap_or(opt.view_width);
     let view_start = match opt.view_start {
         Some(v) => v,
+        _ if opt.boundary.is_finite() => (size as i32 - opt.view_width as i32) / 2,
         _ => -(opt.view_width as i32) / 2,
     };
-    let print_step = match opt.last {
-        Some(v) if v <= opt.steps => opt.steps - v,
-        _ => 0,
+    if let Some(Command::Scan {
+        from,
+        to,
+        rules,
+        mut metrics,
+        sort,
+        format,
+        seed,
+        out_dir,
+    }) = opt.command
+    {
+        let (colors, radius, canonical) = (opt.colors, opt.radius, opt.canonical);
+        let max_nb = kind.try_max_nb(colors, radius)?;
+        let mut rule_nbs: Box<dyn Iterator<Item = BigUint> + Send> = if rules.is_empty() {
+            let mut rule_nb = from.unwrap_or_default();
+            let to = to.unwrap_or(max_nb);
+            Box::new(std::iter::from_fn(move || {
+                if rule_nb > to {
+                    return None;
+                }
+                rule_nb += 1u32;
+                Some(&rule_nb - 1u32)
+            }))
+        } else {
+            Box::new(rules.into_iter())
+        };
+        let rules = std::iter::from_fn(move || loop {
+            let rule = Rule1D::try_with_kind(kind, colors, radius, &rule_nbs.next()?);
+            match rule.and_then(|rule| Ok((!canonical || rule.is_canonical()?, rule))) {
+                Ok((false, _rule)) => continue,
+                Ok((true, rule)) => return Some(Ok(rule)),
+                Err(e) => return Some(Err(e)),
+            }
+        });
+        // Repeated metrics keep their first column
+        let mut seen = Vec::new();
+        metrics.retain(|metric| {
+            let first = !seen.contains(metric);
+            seen.push(*metric);
+            first
+        });
+        let options = scan::ScanOptions {
+            colors,
+            radius,
+            kind,
+            metrics,
+            sort,
+            format,
+            out_dir,
+            classifier: Classifier {
+                size,
+                steps: opt.steps,
+                seed,
+            },
+            boundary: opt.boundary,
+            update: opt.update,
+            size,
+            init: opt.init,
+            view_start,
+            view_width: opt.view_width,
+            steps: opt.steps,
+        };
+        return scan::scan(rules, &options);
+    }
+    let rule = match opt.rule {
+        Some(v) => Rule1D::try_with_kind(kind, opt.colors, opt.radius, &v)?,
+        _ => {
+            let max_nb = kind.try_max_nb(opt.colors, opt.radius)?;
+            // Draws are repeated until a canonical rule, so that each class has the same chance
+            loop {
+                let rule_nb = rng.gen_biguint_below(&(&max_nb + 1u32));
+                let rule = Rule1D::try_with_kind(kind, opt.colors, opt.radius, &rule_nb)?;
+                if !opt.canonical || rule.is_canonical()? {
+                    break rule;
+                }
+            }
+        }
+    };
+    let rule_nb = rule.get_rule_nb();
+    let stochastic = match (opt.noise, opt.domany_kinzel.as_slice()) {
+        (Some(noise), _) => Some((
+            StochasticRule::noisy(&rule, noise)?,
+            format!(
+                "Colors: {}, Radius: {}, Kind: {}, Rule: {}, Noise: {}",
+                opt.colors, opt.radius, kind, rule_nb, noise
+            ),
+        )),
+        (None, &[p1, p2]) => Some((
+            StochasticRule::domany_kinzel(p1, p2)?,
+            format!("Domany-Kinzel: p1 {}, p2 {}", p1, p2),
+        )),
+        (None, &[]) => None,
+        (None, probabilities) => {
+            return Err(Error::InvalidDistribution(format!(
+                "{} Domany-Kinzel probabilities instead of 2",
+                probabilities.len()
+            )))
+        }
+    };
+    if let Some((rule, description)) = stochastic {
+        // Only the commands that follow a single run support the random new cells
+        if let Some(Command::Analyze { .. })
+        | Some(Command::Info)
+        | Some(Command::Preimages { .. }) = opt.command
+        {
+            return Err(Error::StochasticRule);
+        }
+        let mut automata = Automata1D::with_init(
+            rule,
+            opt.boundary,
+            size,
+            &opt.init,
+            view_start,
+            opt.view_width,
+        )?;
+        automata.set_seed(opt.rng_seed);
+        automata.set_update_scheme(opt.update)?;
+        if let Some(line) = run_automata(
+            &mut automata,
+            opt.command,
+            print_step,
+            opt.steps,
+            opt.output,
+        )? {
+            println!(
+                "{}{}{}, Seed: {}",
+                description,
+                update_info(opt.update),
+                line,
+                opt.rng_seed
+            );
+        }
+        return Ok(());
+    }
+    if let Some(Command::Analyze {
+        max_size,
+        max_steps,
+    }) = opt.command
+    {
+        let boundary = if opt.boundary.is_finite() {
+            opt.boundary
+        } else {
+            Boundary::Periodic
+        };
+        analyze(rule, boundary, opt.update, &opt.init, max_size, max_steps)?;
+        println!(
+            "Colors: {}, Radius: {}, Kind: {}, Rule: {}, Boundary: {}{}",
+            opt.colors,
+            opt.radius,
+            kind,
+            rule_nb,
+            boundary,
+            update_info(opt.update)
+        );
+        return Ok(());
+    }
+    if let Some(Command::Info) = opt.command {
+        // The graph describes the synchronous global map
+        if opt.update != UpdateScheme::Synchronous {
+            return Err(Error::UnsupportedUpdateScheme(opt.update));
+        }
+        let graph = DeBruijn::new(&rule)?;
+        println!(
+            "Colors: {}, Radius: {}, Kind: {}, Rule: {}",
+            opt.colors, opt.radius, kind, rule_nb
+        );
+        println!(
+            "De Bruijn graph: {} nodes, {} edges",
+            graph.n_nodes(),
+            graph.n_edges()
+        );
+        println!("Surjective: {}", graph.is_surjective());
+        println!("Injective: {}", graph.is_injective());
+        match graph.garden_of_eden()? {
+            Some(pattern) => println!(
+                "Garden of Eden: {} ({} cells)",
+                pattern
+                    .iter()
+                    .map(|&c| std::char::from_digit(c as u32, 36).unwrap())
+                    .collect::<String>(),
+                pattern.len()
+            ),
+            None => println!("Garden of Eden: none"),
+        }
+        return Ok(());
+    }
+    if let Some(Command::Preimages {
+        depth,
+        max_preimages,
+    }) = opt.command
+    {
+        let mut automata = Automata1D::with_init(
+            rule,
+            opt.boundary,
+            size,
+            &opt.init,
+            view_start,
+            opt.view_width,
+        )?;
+        automata.set_update_scheme(opt.update)?;
+        automata.jump_to(opt.steps.saturating_sub(1))?;
+        let view_width = opt.view_width;
+        let mut rows = opt.output.as_ref().map(|_path| {
+            SpaceTime::new(
+                view_start as i64,
+                automata.get_cur_step() as u64,
+                view_width,
+            )
+        });
+        preimage_tree(&automata, &[], depth, max_preimages, &mut rows)?;
+        if let (Some(image_file), Some(rows)) = (opt.output, rows) {
+            rows.to_image(automata.get_rule())
+                .save_with_format(image_file, image::ImageFormat::Png)?;
+        }
+        println!(
+            "Colors: {}, Radius: {}, Kind: {}, Rule: {}",
+            opt.colors, opt.radius, kind, rule_nb
+        );
+        return Ok(());
+    }
+    if (opt.hashlife || opt.second_order) && opt.update != UpdateScheme::Synchronous {
+        return Err(Error::UnsupportedUpdateScheme(opt.update));
+    }
+    let line = if opt.hashlife {
+        if opt.boundary.is_finite() {
+            return Err(Error::UnsupportedBoundary(opt.boundary));
+        }
+        let mut automata =
+            HashLife1D::with_init(rule, size, &opt.init, view_start, opt.view_width)?;
+        render(&mut automata, print_step, opt.steps, opt.output)?;
+        Some(String::new())
+    } else if opt.second_order {
+        let mut automata = SecondOrderAutomata1D::with_init(
+            rule,
+            opt.boundary,
+            size,
+            &opt.init,
+            view_start,
+            opt.view_width,
+        )?;
+        render(&mut automata, print_step, opt.steps, opt.output)?;
+        Some(String::new())
+    } else {
+        let mut automata = Automata1D::with_init(
+            rule,
+            opt.boundary,
+            size,
+            &opt.init,
+            view_start,
+            opt.view_width,
+        )?;
+        automata.set_update_scheme(opt.update)?;
+        let line = run_automata(
+            &mut automata,
+            opt.command,
+            print_step,
+            opt.steps,
+            opt.output,
+        )?;
+        if let Some(path) = &opt.save_state {
+            automata.save_checkpoint(BufWriter::new(File::create(path)?))?;
+        }
+        line
     };
-    let mut automata = Automata1D::new(rule, view_start, opt.view_width);
+    if let Some(line) = line {
+        println!(
+            "Colors: {}, Radius: {}, Kind: {}, Rule: {}{}{}",
+            opt.colors,
+            opt.radius,
+            kind,
+            rule_nb,
+            update_info(opt.update),
+            line
+        );
+    }
+    Ok(())
+}
+// Print the stats or the damage of the run of 'automata', or print or save its steps.
+// Returns the end of the information line, None when the stats are printed alone.
+fn run_automata<R: CellularRule + Clone>(
+    automata: &mut Automata1D<R>,
+    command: Option<Command>,
+    print_step: u32,
+    steps: u32,
+    output: Option<PathBuf>,
+) -> Result<Option<String>> {
+    match command {
+        Some(Command::Stats { format, max_block }) => {
+            automata.jump_to(print_step)?;
+            let stats = automata.run_stats(steps - print_step, max_block);
+            print_stats(&stats, format)?;
+            Ok(None)
+        }
+        Some(Command::Damage { cell }) => {
+            automata.jump_to(print_step)?;
+            let damage = automata.damage(cell, steps - print_step)?;
+            match output {
+                Some(image_file) => damage
+                    .to_image(automata.get_rule())
+                    .save_with_format(image_file, image::ImageFormat::Png)?,
+                None => print!("{}", damage.to_text(automata.get_rule())),
+            }
+            Ok(Some(format!(
+                ", Left exponent: {}, Right exponent: {}",
+                damage.left_lyapunov, damage.right_lyapunov
+            )))
+        }
+        _ => {
+            render(automata, print_step, steps, output)?;
+            Ok(Some(String::new()))
+        }
+    }
+}
+// Information on the update scheme, nothing for the usual synchronous updates
+fn update_info(update: UpdateScheme) -> String {
+    match update {
+        UpdateScheme::Synchronous => String::new(),
+        update => format!(", Update: {}", update),
+    }
+}
+// Print the cycle reached from 'init' on each lattice size up to 'max_size', that can contain it
+fn analyze(
+    rule: Rule1D,
+    boundary: Boundary,
+    update: UpdateScheme,
+    init: &InitialCondition,
+    max_size: u32,
+    max_steps: u32,
+) -> Result<()> {
+    if update.is_random() {
+        return Err(Error::UnsupportedUpdateScheme(update));
+    }
+    println!(
+        "{:>6} {:>10} {:>10} {:>6}",
+        "size", "transient", "period", "shift"
+    );
+    for size in 1..=max_size {
+        let mut automata = match Automata1D::with_init(rule.clone(), boundary, size, init, 0, size)
+        {
+            Err(Error::InvalidPattern(_)) => continue,
+            automata => automata?,
+        };
+        automata.set_update_scheme(update)?;
+        match automata.find_cycle(max_steps)? {
+            Some(cycle) => println!(
+                "{:>6} {:>10} {:>10} {:>6}",
+                size, cycle.transient, cycle.period, cycle.shift
+            ),
+            None => println!("{:>6} no cycle within {} steps", size, max_steps),
+        }
+    }
+    Ok(())
+}
+// Print the cells with their step, their 'path' in the tree and their number of preimages,
+// then the tree of their first 'max' preimages down to 'depth' steps back.
+// With 'rows', the cells are added to the rows instead of printed.
+fn preimage_tree(
+    automata: &Automata1D,
+    path: &[usize],
+    depth: u32,
+    max: usize,
+    rows: &mut Option<SpaceTime>,
+) -> Result<()> {
+    let mut line = format!("step {}", automata.get_cur_step());
+    if !path.is_empty() {
+        let path = path.iter().map(|i| i.to_string()).collect::<Vec<_>>();
+        line = format!("{}, preimage {}", line, path.join("."));
+    }
+    let line = format!("{}, {} preimages", line, automata.count_preimages()?);
+    match rows {
+        Some(rows) => {
+            rows.push_row(automata.iter());
+            println!("{}", line);
+        }
+        None => println!("{} {}", automata.as_text(), line),
+    }
+    if depth == 0 || automata.get_cur_step() == 0 {
+        return Ok(());
+    }
+    for (i, preimage) in automata.preimages()?.take(max).enumerate() {
+        let path = path.iter().copied().chain(Some(i + 1)).collect::<Vec<_>>();
+        preimage_tree(&preimage, &path, depth - 1, max, rows)?;
+    }
+    Ok(())
+}
+// Print the measures of a run, as CSV lines 'measure,index,value' or as a JSON object.
+// The CSV index is the step for per step measures, the position for temporal entropies
+// and the block size for block entropies.
+fn print_stats(stats: &RunStats, format: scan::Format) -> Result<()> {
+    match format {
+        scan::Format::Json => {
+            let json = serde_json::to_string_pretty(stats)
+                .map_err(|e| Error::Io(std::io::Error::other(e)))?;
+            println!("{}", json);
+        }
+        scan::Format::Csv => {
+            println!("measure,index,value");
+            for (t, densities) in (stats.t_start..).zip(&stats.densities) {
+                for (c, density) in densities.iter().enumerate() {
+                    println!("density_{},{},{}", c, t, density);
+                }
+            }
+            for (t, width) in (stats.t_start..).zip(&stats.active_widths) {
+                println!("active_width,{},{}", t, width);
+            }
+            for (size, entropy) in (1..).zip(&stats.block_entropies) {
+                println!("block_entropy,{},{}", size, entropy);
+            }
+            for (x, entropy) in (stats.x_start..).zip(&stats.temporal_entropies) {
+                println!("temporal_entropy,{},{}", x, entropy);
+            }
+            println!("spreading_rate,,{}", stats.spreading_rate);
+        }
+    }
+    Ok(())
+}
+// Print or save the steps from 'print_step' to 'steps'
+fn render<A: Render>(
+    automata: &mut A,
+    print_step: u32,
+    steps: u32,
+    output: Option<PathBuf>,
+) -> Result<()> {
     if print_step > 0 {
-        automata.step(print_step);
+        automata.skip(print_step)?;
     }
-    if let Some(image_file) = opt.output {
-        let image_buffer = automata.as_image_buffer(opt.steps - print_step);
-        image_buffer
-            .save_with_format(image_file, image::ImageFormat::Png)
-            .unwrap();
+    if let Some(image_file) = output {
+        let image_buffer = automata.as_image_buffer(steps - print_step)?;
+        image_buffer.save_with_format(image_file, image::ImageFormat::Png)?;
     } else {
-        for _i in print_step..opt.steps {
+        for _i in print_step..steps {
             println!("{}", automata.as_text());
-            automata.step(1);
+            automata.step(1)?;
         }
     }
-    println!("Colors: {}, Rule: {}", opt.colors, rule_nb)
+    Ok(())
 }
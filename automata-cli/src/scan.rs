@@ -0,0 +1,499 @@
+use automata_lib::*;
+use rayon::prelude::*;
+use serde_json::{json, Map, Value};
+use std::collections::HashSet;
+use std::fs::{self, File, OpenOptions};
+use std::io::Write;
+use std::path::PathBuf;
+use std::sync::Mutex;
+
+// Block size of the block entropy of the stats metric
+const SCAN_BLOCK: usize = 4;
+
+// Measures computed for each rule
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum Metric {
+    // Classification and the signals it is based on
+    Class,
+    // Measures of the rendered run
+    Stats,
+}
+impl Metric {
+    fn columns(self) -> &'static [&'static str] {
+        match self {
+            Metric::Class => &[
+                "class",
+                "confidence",
+                "compression",
+                "density_variation",
+                "entropy_variation",
+                "transient",
+                "period",
+                "lyapunov",
+            ],
+            Metric::Stats => &["spreading_rate", "block_entropy", "temporal_entropy"],
+        }
+    }
+}
+impl std::str::FromStr for Metric {
+    type Err = String;
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        match s {
+            "class" => Ok(Metric::Class),
+            "stats" => Ok(Metric::Stats),
+            _ => Err(format!("Unknown metric '{}'", s)),
+        }
+    }
+}
+#[derive(Clone, Copy)]
+pub enum Format {
+    Csv,
+    Json,
+}
+impl std::str::FromStr for Format {
+    type Err = String;
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        match s {
+            "csv" => Ok(Format::Csv),
+            "json" => Ok(Format::Json),
+            _ => Err(format!("Unknown format '{}'", s)),
+        }
+    }
+}
+// Column the report is sorted by, in increasing order except for the confidence
+#[derive(Clone, Copy)]
+pub enum ScanSort {
+    Rule,
+    Column(&'static str),
+}
+impl std::str::FromStr for ScanSort {
+    type Err = String;
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        if s == "rule" {
+            return Ok(ScanSort::Rule);
+        }
+        [Metric::Class, Metric::Stats]
+            .iter()
+            .flat_map(|metric| metric.columns())
+            .find(|&&column| column == s)
+            .map(|&column| ScanSort::Column(column))
+            .ok_or_else(|| format!("Unknown scan order '{}'", s))
+    }
+}
+pub struct ScanOptions {
+    // Rules of the scan
+    pub colors: u8,
+    pub radius: u8,
+    pub kind: RuleKind,
+    pub metrics: Vec<Metric>,
+    pub sort: ScanSort,
+    pub format: Format,
+    // Directory of the thumbnails and of the index, the report is printed when not provided
+    pub out_dir: Option<PathBuf>,
+    pub classifier: Classifier,
+    // Rendered run of each rule, for the thumbnails and the stats metric
+    pub boundary: Boundary,
+    pub update: UpdateScheme,
+    pub size: u32,
+    pub init: InitialCondition,
+    pub view_start: i32,
+    pub view_width: u32,
+    pub steps: u32,
+}
+// Measures of one rule, in the order of the columns
+struct Record {
+    rule_nb: BigUint,
+    values: Vec<Value>,
+}
+// Measure all the rules, on all the cores.
+// With an output directory, each rule has a PNG thumbnail and a line in the index, written as soon as
+// the rule is measured, so that an interrupted scan only measures the rules that are not in the index.
+// The index is then sorted like the report.
+pub fn scan<I>(rules: I, options: &ScanOptions) -> Result<()>
+where
+    I: Iterator<Item = Result<Rule1D>> + Send,
+{
+    let columns = options
+        .metrics
+        .iter()
+        .flat_map(|metric| metric.columns())
+        .copied()
+        .collect::<Vec<_>>();
+    if let ScanSort::Column(column) = options.sort {
+        if !columns.contains(&column) {
+            return Err(invalid_data(format!(
+                "Cannot sort by {}, it is not a column of the chosen metrics",
+                column
+            )));
+        }
+    }
+    let index = match &options.out_dir {
+        Some(dir) => Some(Index::open(dir, options, &columns)?),
+        None => None,
+    };
+    let records = Mutex::new(Vec::new());
+    rules.par_bridge().try_for_each(|rule| -> Result<()> {
+        let rule = rule?;
+        let rule_nb = rule.get_rule_nb();
+        if let Some(index) = &index {
+            if index.done.contains(&rule_nb) {
+                return Ok(());
+            }
+        }
+        let values = measure(&rule, options)?;
+        let record = Record { rule_nb, values };
+        match &index {
+            Some(index) => index.append(&record)?,
+            None => records.lock().unwrap().push(record),
+        }
+        Ok(())
+    })?;
+    match index {
+        Some(index) => {
+            let (n_appended, n_done) = (*index.n_appended.lock().unwrap(), index.done.len());
+            let path = index.path.clone();
+            index.finish(options.sort)?;
+            println!(
+                "Scanned {} rules, {} were already in {}",
+                n_appended,
+                n_done,
+                path.display()
+            );
+        }
+        None => {
+            let mut records = records.into_inner().unwrap();
+            sort(&mut records, &columns, options.sort);
+            print_report(&records, &columns, options.format)?;
+        }
+    }
+    Ok(())
+}
+// Values of the columns of the metrics, in the order of the metrics
+fn measure(rule: &Rule1D, options: &ScanOptions) -> Result<Vec<Value>> {
+    let mut stats = None;
+    if options.out_dir.is_some() || options.metrics.contains(&Metric::Stats) {
+        let mut automata = Automata1D::with_init(
+            rule.clone(),
+            options.boundary,
+            options.size,
+            &options.init,
+            options.view_start,
+            options.view_width,
+        )?;
+        automata.set_update_scheme(options.update)?;
+        let (diagram, run_stats) = automata.space_time_with_stats(options.steps, SCAN_BLOCK);
+        if let Some(dir) = &options.out_dir {
+            let path = dir.join(thumbnail_name(rule));
+            diagram
+                .to_image(rule)
+                .save_with_format(path, image::ImageFormat::Png)?;
+        }
+        stats = Some(run_stats);
+    }
+    let mut values = Vec::new();
+    for metric in options.metrics.iter() {
+        match metric {
+            Metric::Class => {
+                let c = options.classifier.classify(rule)?;
+                let signals = &c.signals;
+                values.extend(vec![
+                    Value::from(c.class as u8),
+                    float(c.confidence),
+                    float(signals.compression),
+                    float(signals.density_variation),
+                    float(signals.entropy_variation),
+                    signals
+                        .cycle
+                        .map_or(Value::Null, |cycle| cycle.transient.into()),
+                    signals
+                        .cycle
+                        .map_or(Value::Null, |cycle| cycle.period.into()),
+                    float(signals.lyapunov),
+                ]);
+            }
+            Metric::Stats => {
+                let stats = stats.as_ref().unwrap();
+                let n_positions = stats.temporal_entropies.len().max(1) as f64;
+                let temporal_entropy = stats.temporal_entropies.iter().sum::<f64>() / n_positions;
+                values.extend(vec![
+                    float(stats.spreading_rate),
+                    float(stats.block_entropies[SCAN_BLOCK - 1]),
+                    float(temporal_entropy),
+                ]);
+            }
+        }
+    }
+    Ok(values)
+}
+fn thumbnail_name(rule: &Rule1D) -> String {
+    format!(
+        "{}C_r{}_{}_{}.png",
+        rule.get_n_colors(),
+        rule.get_radius(),
+        rule.get_kind(),
+        rule.get_rule_nb()
+    )
+}
+// Measures are rounded, for a readable report
+fn float(value: f64) -> Value {
+    Value::from((value * 1e4).round() / 1e4)
+}
+fn sort(records: &mut [Record], columns: &[&str], sort: ScanSort) {
+    match sort {
+        ScanSort::Rule => records.sort_by(|a, b| a.rule_nb.cmp(&b.rule_nb)),
+        ScanSort::Column(column) => {
+            let i = columns.iter().position(|&c| c == column).unwrap();
+            // Missing values, like the period of rules without cycle, are last
+            let key = |record: &Record| record.values[i].as_f64().unwrap_or(f64::INFINITY);
+            records.sort_by(|a, b| {
+                let order = key(a).total_cmp(&key(b));
+                let order = if column == "confidence" {
+                    order.reverse()
+                } else {
+                    order
+                };
+                order.then(a.rule_nb.cmp(&b.rule_nb))
+            });
+        }
+    }
+}
+fn print_report(records: &[Record], columns: &[&str], format: Format) -> Result<()> {
+    match format {
+        Format::Csv => {
+            println!("{}", csv_header(columns));
+            for record in records {
+                println!("{}", csv_line(record));
+            }
+        }
+        Format::Json => {
+            let report = records
+                .iter()
+                .map(|record| json_object(record, columns))
+                .collect::<Vec<_>>();
+            let json = serde_json::to_string_pretty(&report).map_err(invalid_data)?;
+            println!("{}", json);
+        }
+    }
+    Ok(())
+}
+fn csv_header(columns: &[&str]) -> String {
+    std::iter::once("rule")
+        .chain(columns.iter().copied())
+        .collect::<Vec<_>>()
+        .join(",")
+}
+fn csv_line(record: &Record) -> String {
+    std::iter::once(record.rule_nb.to_string())
+        .chain(record.values.iter().map(|value| match value {
+            Value::Null => String::new(),
+            value => value.to_string(),
+        }))
+        .collect::<Vec<_>>()
+        .join(",")
+}
+// Rule numbers can be too large for JSON numbers, they are strings
+fn json_object(record: &Record, columns: &[&str]) -> Value {
+    let mut object = Map::new();
+    object.insert(
+        String::from("rule"),
+        Value::from(record.rule_nb.to_string()),
+    );
+    for (column, value) in columns.iter().zip(&record.values) {
+        object.insert(column.to_string(), value.clone());
+    }
+    Value::Object(object)
+}
+// Parameters the measures and thumbnails depend on, an index is only resumed with the same parameters
+fn parameters(options: &ScanOptions) -> Value {
+    json!({
+        "colors": options.colors,
+        "radius": options.radius,
+        "kind": options.kind.to_string(),
+        "boundary": options.boundary.to_string(),
+        "update": options.update.to_string(),
+        "size": options.size,
+        "init": options.init.to_string(),
+        "view_start": options.view_start,
+        "view_width": options.view_width,
+        "steps": options.steps,
+        "classifier": {
+            "size": options.classifier.size,
+            "steps": options.classifier.steps,
+            "seed": options.classifier.seed,
+        },
+    })
+}
+fn invalid_data<E: Into<Box<dyn std::error::Error + Send + Sync>>>(e: E) -> Error {
+    Error::Io(std::io::Error::new(std::io::ErrorKind::InvalidData, e))
+}
+// Index of the scanned rules, index.csv with a header line, or index.jsonl with one JSON object per line,
+// next to scan.json with the parameters of the scan
+struct Index {
+    path: PathBuf,
+    format: Format,
+    columns: Vec<&'static str>,
+    file: Mutex<File>,
+    // Rules already in the index when the scan started
+    done: HashSet<BigUint>,
+    n_appended: Mutex<usize>,
+}
+impl Index {
+    fn open(dir: &PathBuf, options: &ScanOptions, columns: &[&'static str]) -> Result<Index> {
+        let format = options.format;
+        fs::create_dir_all(dir)?;
+        let path = dir.join(match format {
+            Format::Csv => "index.csv",
+            Format::Json => "index.jsonl",
+        });
+        let parameters_path = dir.join("scan.json");
+        let parameters = parameters(options);
+        let mut done = HashSet::new();
+        if path.exists() {
+            let written = match fs::read_to_string(&parameters_path) {
+                Ok(content) => serde_json::from_str::<Value>(&content).ok(),
+                Err(_e) => None,
+            };
+            if written.as_ref() != Some(&parameters) {
+                return Err(invalid_data(format!(
+                    "{} was written with other scan parameters than {}",
+                    path.display(),
+                    parameters
+                )));
+            }
+            // An interrupted scan can leave an incomplete last line, it is dropped
+            let content = fs::read_to_string(&path)?;
+            let complete = content.rfind('\n').map_or(0, |i| i + 1);
+            if complete < content.len() {
+                OpenOptions::new()
+                    .write(true)
+                    .open(&path)?
+                    .set_len(complete as u64)?;
+            }
+            let mut lines = content[..complete].lines();
+            if let Format::Csv = format {
+                if let Some(header) = lines.next() {
+                    if header != csv_header(columns) {
+                        return Err(invalid_data(format!(
+                            "{} was written with other metrics",
+                            path.display()
+                        )));
+                    }
+                }
+            }
+            for line in lines {
+                if let Format::Json = format {
+                    let object =
+                        serde_json::from_str::<Map<String, Value>>(line).map_err(invalid_data)?;
+                    if object.len() != columns.len() + 1
+                        || columns.iter().any(|&column| !object.contains_key(column))
+                    {
+                        return Err(invalid_data(format!(
+                            "{} was written with other metrics",
+                            path.display()
+                        )));
+                    }
+                }
+                let record = parse_record(line, format, columns)
+                    .ok_or_else(|| invalid_data(format!("Invalid line in {}", path.display())))?;
+                done.insert(record.rule_nb);
+            }
+        } else {
+            fs::write(&parameters_path, format!("{}\n", parameters))?;
+        }
+        let mut file = OpenOptions::new().create(true).append(true).open(&path)?;
+        if let (Format::Csv, 0) = (format, file.metadata()?.len()) {
+            writeln!(file, "{}", csv_header(columns))?;
+        }
+        Ok(Index {
+            path,
+            format,
+            columns: columns.to_vec(),
+            file: Mutex::new(file),
+            done,
+            n_appended: Mutex::new(0),
+        })
+    }
+    // Lines are written at once, so that lines of parallel rules do not mix
+    fn append(&self, record: &Record) -> Result<()> {
+        let line = match self.format {
+            Format::Csv => csv_line(record),
+            Format::Json => json_object(record, &self.columns).to_string(),
+        };
+        self.file
+            .lock()
+            .unwrap()
+            .write_all(format!("{}\n", line).as_bytes())?;
+        *self.n_appended.lock().unwrap() += 1;
+        Ok(())
+    }
+    // Lines are rewritten in the order of the report once all the rules are measured,
+    // in a temporary file first so that an interrupted rewrite keeps the index.
+    fn finish(self, order: ScanSort) -> Result<()> {
+        let Index {
+            path,
+            format,
+            columns,
+            file,
+            ..
+        } = self;
+        // The index is closed before it is replaced
+        drop(file);
+        let content = fs::read_to_string(&path)?;
+        let mut lines = content.lines();
+        let mut sorted = String::new();
+        if let Format::Csv = format {
+            sorted.push_str(&format!("{}\n", csv_header(&columns)));
+            lines.next();
+        }
+        let mut records = lines
+            .map(|line| {
+                parse_record(line, format, &columns)
+                    .ok_or_else(|| invalid_data(format!("Invalid line in {}", path.display())))
+            })
+            .collect::<Result<Vec<_>>>()?;
+        sort(&mut records, &columns, order);
+        for record in records.iter() {
+            let line = match format {
+                Format::Csv => csv_line(record),
+                Format::Json => json_object(record, &columns).to_string(),
+            };
+            sorted.push_str(&format!("{}\n", line));
+        }
+        let tmp_path = path.with_extension("tmp");
+        fs::write(&tmp_path, sorted)?;
+        fs::rename(&tmp_path, &path)?;
+        Ok(())
+    }
+}
+// Record of a line of the index, None when the line is not one of these columns
+fn parse_record(line: &str, format: Format, columns: &[&str]) -> Option<Record> {
+    let (rule_nb, values) = match format {
+        Format::Csv => {
+            let mut fields = line.split(',');
+            let rule_nb = fields.next()?.to_string();
+            let values = fields
+                .map(|field| match field {
+                    "" => Some(Value::Null),
+                    field => serde_json::from_str::<Value>(field).ok(),
+                })
+                .collect::<Option<Vec<_>>>()?;
+            (rule_nb, values)
+        }
+        Format::Json => {
+            let object = serde_json::from_str::<Map<String, Value>>(line).ok()?;
+            let rule_nb = object.get("rule")?.as_str()?.to_string();
+            let values = columns
+                .iter()
+                .map(|&column| object.get(column).cloned())
+                .collect::<Option<Vec<_>>>()?;
+            (rule_nb, values)
+        }
+    };
+    if values.len() != columns.len() {
+        return None;
+    }
+    Some(Record {
+        rule_nb: rule_nb.parse().ok()?,
+        values,
+    })
+}
@@ -1,3 +1,4 @@
+extern crate gdk;
 extern crate gio;
 extern crate gtk;
 
@@ -6,8 +7,12 @@ use gdk_pixbuf::{Colorspace, Pixbuf};
 use gio::prelude::*;
 use glib::clone;
 use gtk::prelude::*;
-use rand::{thread_rng, Rng};
+use num_bigint::RandBigInt;
+use rand::thread_rng;
 use std::env;
+use std::fs::File;
+use std::io::{BufReader, BufWriter};
+use std::path::Path;
 use std::sync::{Arc, Mutex};
 use std::thread;
 use std::time::Duration;
@@ -16,17 +21,39 @@ enum Message {
     UpdatePlayButton(bool),                       // is_playing
     ResetDrawing(i32, i32),                       // width, height
     DrawStripe(i32, i32, i32, Vec<(u8, u8, u8)>), // row, width, height, rbg_vec
-    SetRuleNb(u64),                               // value
+    SetRuleNb(BigUint),                           // value
     SetNColors(u8),                               // value
+    SetRadius(u8),                                // value
+    SetKind(RuleKind),                            // value
+    SetBoundary(Boundary),                        // value
     SetWidth(i32),                                // value
     SetHeight(i32),                               // value
     SetStepNb(u32),                               // value
+    SetStatus(String),                            // error message, empty when valid
 }
 
+// Boundaries selectable in the boundary combo, in the same order
+const BOUNDARIES: [Boundary; 4] = [
+    Boundary::Infinite,
+    Boundary::Periodic,
+    Boundary::Fixed(0),
+    Boundary::Reflective,
+];
+
+// Number of past steps kept for the scroll-back
+const MAX_HISTORY_ROWS: u32 = 10_000;
+
 struct AutomataModel {
     automata: Option<Automata1D>,
+    // Steps computed since the last reset, up to MAX_HISTORY_ROWS
+    history: Option<SpaceTime>,
+    // Number of steps scrolled back from the last one, while paused
+    scroll: u32,
     n_colors: u8,
-    rule_nb: u64,
+    radius: u8,
+    kind: RuleKind,
+    rule_nb: BigUint,
+    boundary: Boundary,
     width: i32,
     height: i32,
     continuous: bool,
@@ -39,8 +66,13 @@ impl AutomataModel {
     fn new() -> AutomataModel {
         AutomataModel {
             automata: None,
+            history: None,
+            scroll: 0,
             n_colors: 0,
-            rule_nb: 0,
+            radius: 0,
+            kind: RuleKind::SymmetricOrdered,
+            rule_nb: BigUint::from(0u32),
+            boundary: Boundary::Infinite,
             width: 0,
             height: 0,
             continuous: false,
@@ -52,7 +84,10 @@ impl AutomataModel {
     }
     fn initialize(&mut self) {
         self.set_n_colors(3);
-        self.set_rule_nb(40327);
+        self.set_radius(1);
+        self.set_kind(RuleKind::SymmetricOrdered);
+        self.set_rule_nb(BigUint::from(40327u32));
+        self.set_boundary(Boundary::Infinite);
         self.set_width(1600);
         self.set_height(800);
         self.set_continous(true);
@@ -69,11 +104,23 @@ impl AutomataModel {
         }
         self.clean = true;
         self.cur_row = 0;
+        self.history = None;
+        self.scroll = 0;
         self.stop_playing();
     }
     fn play(&mut self, n_steps: i32) {
-        if self.playing {
-            let rgb_vec = self.automata.as_mut().unwrap().as_rgb_vec(n_steps as u32);
+        if let (true, Some(automata)) = (self.playing, self.automata.as_mut()) {
+            let diagram = automata.space_time(n_steps as u32);
+            let rgb_vec = diagram.to_rgb_vec(automata.get_rule());
+            let cur_step = automata.get_cur_step();
+            match self.history.as_mut() {
+                Some(history) => {
+                    history.append(&diagram);
+                    let excess = history.height().saturating_sub(MAX_HISTORY_ROWS);
+                    history.drop_first_rows(excess);
+                }
+                None => self.history = Some(diagram),
+            }
             self.tx
                 .as_ref()
                 .unwrap()
@@ -87,9 +134,7 @@ impl AutomataModel {
             self.tx
                 .as_ref()
                 .unwrap()
-                .send(Message::SetStepNb(
-                    self.automata.as_ref().unwrap().get_cur_step(),
-                ))
+                .send(Message::SetStepNb(cur_step))
                 .unwrap();
             self.cur_row += n_steps;
             if !self.continuous && self.cur_row >= self.height {
@@ -105,7 +150,7 @@ impl AutomataModel {
     fn switch_playing(&mut self) {
         if self.playing {
             self.stop_playing();
-        } else if self.width > 0 && self.height > 0 {
+        } else if self.width > 0 && self.height > 0 && self.automata.is_some() {
             self.start_playing();
         }
     }
@@ -114,6 +159,10 @@ impl AutomataModel {
             self.reset();
             self.clean = true;
         }
+        if self.scroll != 0 {
+            self.scroll = 0;
+            self.draw_history();
+        }
         self.playing = true;
         self.tx
             .as_ref()
@@ -129,15 +178,125 @@ impl AutomataModel {
             .send(Message::UpdatePlayButton(false))
             .unwrap();
     }
+    // Move the view 'rows' steps back in the history, or forward when negative
+    fn scroll_back(&mut self, rows: i32) {
+        if let (false, Some(history)) = (self.playing, self.history.as_ref()) {
+            let max_scroll = history.height().saturating_sub(self.height as u32);
+            let scroll = (self.scroll as i64 + rows as i64).clamp(0, max_scroll as i64) as u32;
+            if scroll != self.scroll {
+                self.scroll = scroll;
+                self.draw_history();
+            }
+        }
+    }
+    // Redraw the image with the history steps, up to 'scroll' steps before the last one
+    fn draw_history(&mut self) {
+        if let Some(history) = self.history.as_ref() {
+            let end = history.t_start() + (history.height() - self.scroll) as u64;
+            let n_rows = (self.height as u64).min(end - history.t_start());
+            let view = history.crop(
+                history.x_start(),
+                end - n_rows,
+                history.width(),
+                n_rows as u32,
+            );
+            let rgb_vec = view.to_rgb_vec(self.automata.as_ref().unwrap().get_rule());
+            let tx = self.tx.as_ref().unwrap();
+            tx.send(Message::ResetDrawing(self.width, self.height))
+                .unwrap();
+            tx.send(Message::DrawStripe(
+                0,
+                view.width() as i32,
+                view.height() as i32,
+                rgb_vec,
+            ))
+            .unwrap();
+            tx.send(Message::SetStepNb(end as u32)).unwrap();
+        }
+    }
     fn reset_automata(&mut self) {
-        let rule = Rule1D::new(self.n_colors, self.rule_nb);
-        self.automata = Some(Automata1D::new(rule, -self.width / 2, self.width as u32));
+        // A finite lattice is exactly as wide as the view
+        let view_start = if self.boundary.is_finite() {
+            0
+        } else {
+            -self.width / 2
+        };
+        let width = self.width as u32;
+        let automata = Rule1D::try_with_kind(self.kind, self.n_colors, self.radius, &self.rule_nb)
+            .and_then(|rule| {
+                Automata1D::with_boundary(rule, self.boundary, width, view_start, width)
+            });
+        let status = match automata {
+            Ok(automata) => {
+                self.automata = Some(automata);
+                String::new()
+            }
+            Err(e) => {
+                self.automata = None;
+                e.to_string()
+            }
+        };
+        self.tx
+            .as_ref()
+            .unwrap()
+            .send(Message::SetStatus(status))
+            .unwrap();
+    }
+    // Save the automata state, to resume it later with load_session
+    fn save_session(&mut self, path: &Path) {
+        let saved = match &self.automata {
+            Some(automata) => File::create(path)
+                .map_err(Error::from)
+                .and_then(|file| automata.save_checkpoint(BufWriter::new(file))),
+            None => Ok(()),
+        };
+        let status = saved.err().map(|e| e.to_string()).unwrap_or_default();
+        self.tx
+            .as_ref()
+            .unwrap()
+            .send(Message::SetStatus(status))
+            .unwrap();
+    }
+    // Replace the automata by the one saved in 'path', the parameters follow its rule and view
+    fn load_session(&mut self, path: &Path) {
+        let loaded = File::open(path)
+            .map_err(Error::from)
+            .and_then(|file| Automata1D::load_checkpoint(BufReader::new(file)));
+        match loaded {
+            Ok(automata) => {
+                let rule = automata.get_rule();
+                self.set_n_colors(rule.get_n_colors());
+                self.set_radius(rule.get_radius());
+                self.set_kind(rule.get_kind());
+                self.set_rule_nb(rule.get_rule_nb());
+                self.set_boundary(automata.get_boundary());
+                self.set_width(automata.get_view_width() as i32);
+                let cur_step = automata.get_cur_step();
+                self.automata = Some(automata);
+                self.history = None;
+                self.scroll = 0;
+                let tx = self.tx.as_ref().unwrap();
+                tx.send(Message::ResetDrawing(self.width, self.height))
+                    .unwrap();
+                tx.send(Message::SetStepNb(cur_step)).unwrap();
+                tx.send(Message::SetStatus(String::new())).unwrap();
+                self.clean = true;
+                self.cur_row = 0;
+                self.stop_playing();
+            }
+            Err(e) => self
+                .tx
+                .as_ref()
+                .unwrap()
+                .send(Message::SetStatus(e.to_string()))
+                .unwrap(),
+        }
     }
     fn set_n_colors(&mut self, n_colors: u8) {
         let filtered = if n_colors < 2 {
             2
-        } else if n_colors > 4 {
-            4
+        } else if n_colors > MAX_COLORS {
+            MAX_COLORS
         } else {
             n_colors
         };
@@ -152,18 +311,70 @@ impl AutomataModel {
         }
         // Rule_nb can become illegal, try to setting it again
         // This will legalize it
-        self.set_rule_nb(self.rule_nb);
+        self.set_rule_nb(self.rule_nb.clone());
+    }
+    fn set_radius(&mut self, radius: u8) {
+        let filtered = if radius < 1 {
+            1
+        } else if radius > 3 {
+            3
+        } else {
+            radius
+        };
+        if filtered != self.radius {
+            self.radius = filtered;
+            self.clean = false;
+            self.tx
+                .as_ref()
+                .unwrap()
+                .send(Message::SetRadius(self.radius))
+                .unwrap();
+        }
+        // Rule_nb can become illegal, try to setting it again
+        // This will legalize it
+        self.set_rule_nb(self.rule_nb.clone());
     }
-    fn set_rule_nb(&mut self, rule_nb: u64) {
-        let max = Rule1D::get_max_nb(self.n_colors);
-        let filtered = if rule_nb >= max { max } else { rule_nb };
+    fn set_kind(&mut self, kind: RuleKind) {
+        if kind != self.kind {
+            self.kind = kind;
+            self.clean = false;
+            self.tx
+                .as_ref()
+                .unwrap()
+                .send(Message::SetKind(self.kind))
+                .unwrap();
+        }
+        // Rule_nb can become illegal, try to setting it again
+        // This will legalize it
+        self.set_rule_nb(self.rule_nb.clone());
+    }
+    fn set_rule_nb(&mut self, rule_nb: BigUint) {
+        // When the rule space itself is invalid, the error is reported on reset
+        let filtered = match self.get_max_rule_nb() {
+            Ok(max) if rule_nb >= max => max,
+            _ => rule_nb,
+        };
         if filtered != self.rule_nb {
             self.rule_nb = filtered;
             self.clean = false;
             self.tx
                 .as_ref()
                 .unwrap()
-                .send(Message::SetRuleNb(self.rule_nb))
+                .send(Message::SetRuleNb(self.rule_nb.clone()))
+                .unwrap();
+        }
+    }
+    fn get_max_rule_nb(&self) -> Result<BigUint> {
+        self.kind.try_max_nb(self.n_colors, self.radius)
+    }
+    fn set_boundary(&mut self, boundary: Boundary) {
+        if boundary != self.boundary {
+            self.boundary = boundary;
+            self.clean = false;
+            self.tx
+                .as_ref()
+                .unwrap()
+                .send(Message::SetBoundary(self.boundary))
                 .unwrap();
         }
     }
@@ -223,7 +434,10 @@ fn build_ui(app: &gtk::Application, model: Arc<Mutex<AutomataModel>>) {
     window.set_application(Some(app));
 
     let n_colors_combo: gtk::ComboBox = builder.get_object("n_colors_combo").unwrap();
+    let radius_combo: gtk::ComboBox = builder.get_object("radius_combo").unwrap();
+    let kind_combo: gtk::ComboBox = builder.get_object("kind_combo").unwrap();
     let rule_nb_entry: gtk::Entry = builder.get_object("rule_nb_entry").unwrap();
+    let boundary_combo: gtk::ComboBox = builder.get_object("boundary_combo").unwrap();
     let width_entry: gtk::Entry = builder.get_object("width_entry").unwrap();
     let height_entry: gtk::Entry = builder.get_object("height_entry").unwrap();
     let rule_rand_btn: gtk::Button = builder.get_object("rule_rand_btn").unwrap();
@@ -233,9 +447,14 @@ fn build_ui(app: &gtk::Application, model: Arc<Mutex<AutomataModel>>) {
     let save_btn: gtk::Button = builder.get_object("save_btn").unwrap();
     let continuous_chk: gtk::CheckButton = builder.get_object("continuous_chk").unwrap();
     let save_dlg: gtk::FileChooserDialog = builder.get_object("save_file_dlg").unwrap();
+    let session_save_btn: gtk::Button = builder.get_object("session_save_btn").unwrap();
+    let session_load_btn: gtk::Button = builder.get_object("session_load_btn").unwrap();
+    let session_save_dlg: gtk::FileChooserDialog = builder.get_object("session_save_dlg").unwrap();
+    let session_load_dlg: gtk::FileChooserDialog = builder.get_object("session_load_dlg").unwrap();
     let play_img: gtk::Image = builder.get_object("icon_play").unwrap();
     let pause_img: gtk::Image = builder.get_object("icon_pause").unwrap();
     let step_nb_label: gtk::Label = builder.get_object("step_nb_label").unwrap();
+    let status_label: gtk::Label = builder.get_object("status_label").unwrap();
 
     let (tx, rx) = glib::MainContext::channel(glib::PRIORITY_HIGH);
     {
@@ -248,12 +467,27 @@ fn build_ui(app: &gtk::Application, model: Arc<Mutex<AutomataModel>>) {
         let mut m = model.lock().unwrap();
         m.set_n_colors(val as u8);
     }));
+    radius_combo.connect_changed(clone!(@weak model => move |combo| {
+        let val = combo.get_active().unwrap()+1;
+        let mut m = model.lock().unwrap();
+        m.set_radius(val as u8);
+    }));
+    kind_combo.connect_changed(clone!(@weak model => move |combo| {
+        let val = combo.get_active().unwrap();
+        let mut m = model.lock().unwrap();
+        m.set_kind(RuleKind::ALL[val as usize]);
+    }));
     rule_nb_entry.connect_changed(clone!(@weak model => move |entry| {
         let text = filter_integer(&entry);
-        let val = text.parse::<u64>().unwrap_or(0);
+        let val = text.parse::<BigUint>().unwrap_or_default();
         let mut m = model.lock().unwrap( );
         m.set_rule_nb(val);
     }));
+    boundary_combo.connect_changed(clone!(@weak model => move |combo| {
+        let val = combo.get_active().unwrap();
+        let mut m = model.lock().unwrap();
+        m.set_boundary(BOUNDARIES[val as usize]);
+    }));
     height_entry.connect_changed(clone!(@weak model => move |entry| {
         let text = filter_integer(&entry);
         let val = text.parse::<i32>().unwrap_or(0);
@@ -270,15 +504,21 @@ fn build_ui(app: &gtk::Application, model: Arc<Mutex<AutomataModel>>) {
         let mut m = model.lock().unwrap();
         m.set_continous(entry.get_active());
     }));
-    rule_rand_btn.connect_clicked(clone!(@weak rule_nb_entry => move |_| {
+    rule_rand_btn.connect_clicked(clone!(@weak model, @weak rule_nb_entry => move |_| {
         let mut rng = thread_rng();
-        let rule_id = rng.gen_range(0, 81 * 729);
-        rule_nb_entry.set_text(&rule_id.to_string());
+        let max = model.lock().unwrap().get_max_rule_nb();
+        if let Ok(max) = max {
+            let rule_id = rng.gen_biguint_below(&(max + 1u32));
+            rule_nb_entry.set_text(&rule_id.to_string());
+        }
     }));
     save_btn.connect_clicked(clone!(@weak model,@weak display_img => move |_| {
         save_dlg.show();
-        let rule_nb = (*model.lock().unwrap()).rule_nb;
-        save_dlg.set_current_name(format!("3C_{}.png",rule_nb));
+        let (n_colors, rule_nb) = {
+            let m = model.lock().unwrap();
+            (m.n_colors, m.rule_nb.clone())
+        };
+        save_dlg.set_current_name(format!("{}C_{}.png",n_colors,rule_nb));
         if save_dlg.run() == gtk::ResponseType::Ok {
             if let Some(filename) = save_dlg.get_filename() {
                 display_img.get_pixbuf().unwrap().savev(filename,"png",&[]).unwrap();
@@ -286,6 +526,42 @@ fn build_ui(app: &gtk::Application, model: Arc<Mutex<AutomataModel>>) {
         }
         save_dlg.hide();
     }));
+    session_save_btn.connect_clicked(clone!(@weak model => move |_| {
+        session_save_dlg.show();
+        let (n_colors, rule_nb) = {
+            let m = model.lock().unwrap();
+            (m.n_colors, m.rule_nb.clone())
+        };
+        session_save_dlg.set_current_name(format!("{}C_{}.ca",n_colors,rule_nb));
+        if session_save_dlg.run() == gtk::ResponseType::Ok {
+            if let Some(filename) = session_save_dlg.get_filename() {
+                model.lock().unwrap().save_session(&filename);
+            }
+        }
+        session_save_dlg.hide();
+    }));
+    session_load_btn.connect_clicked(clone!(@weak model => move |_| {
+        session_load_dlg.show();
+        if session_load_dlg.run() == gtk::ResponseType::Ok {
+            if let Some(filename) = session_load_dlg.get_filename() {
+                model.lock().unwrap().load_session(&filename);
+            }
+        }
+        session_load_dlg.hide();
+    }));
+    // While paused, the mouse wheel scrolls back in the steps history
+    window.add_events(gdk::EventMask::SCROLL_MASK);
+    window.connect_scroll_event(
+        clone!(@weak model => @default-return gtk::Inhibit(false), move |_, event| {
+            let rows = match event.get_direction() {
+                gdk::ScrollDirection::Up => 20,
+                gdk::ScrollDirection::Down => -20,
+                _ => 0,
+            };
+            model.lock().unwrap().scroll_back(rows);
+            gtk::Inhibit(false)
+        }),
+    );
     play_btn.connect_clicked(clone!(@weak model => move |_| {
         let mut m = model.lock().unwrap();
         m.switch_playing();
@@ -319,6 +595,19 @@ fn build_ui(app: &gtk::Application, model: Arc<Mutex<AutomataModel>>) {
             Message::SetNColors(value) => {
                 n_colors_combo.set_active(Some((value - 2) as u32));
             }
+            Message::SetRadius(value) => {
+                radius_combo.set_active(Some((value - 1) as u32));
+            }
+            Message::SetKind(value) => {
+                let idx = RuleKind::ALL.iter().position(|&k| k == value).unwrap();
+                kind_combo.set_active(Some(idx as u32));
+            }
+            Message::SetBoundary(value) => {
+                // Sessions saved elsewhere can use a boundary that is not in the combo
+                if let Some(idx) = BOUNDARIES.iter().position(|&b| b == value) {
+                    boundary_combo.set_active(Some(idx as u32));
+                }
+            }
             Message::SetWidth(value) => {
                 width_entry.set_text(&value.to_string());
             }
@@ -328,6 +617,9 @@ fn build_ui(app: &gtk::Application, model: Arc<Mutex<AutomataModel>>) {
             Message::SetStepNb(value) => {
                 step_nb_label.set_text(&value.to_string());
             }
+            Message::SetStatus(value) => {
+                status_label.set_text(&value);
+            }
             Message::DrawStripe(row, width, height, rgb_vec) => {
                 let pixbuf = display_img.get_pixbuf().unwrap();
                 let mut real_row = row;
@@ -0,0 +1,57 @@
+use automata_lib::*;
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion};
+
+// Time of 100 steps, after the automata already ran for 'warmup' steps.
+// For a pattern that does not grow, like rule 4, or a finite lattice, it must not depend on 'warmup'.
+fn step_benchmark(c: &mut Criterion) {
+    let mut group = c.benchmark_group("step_100");
+    for &warmup in [0u32, 10_000, 100_000].iter() {
+        // Rule 4 with 3 colors, the lone cell of the last color stays, stepped on bytes
+        let rule = Rule1D::with_kind(RuleKind::Full, 3, 1, &BigUint::from(1458u32));
+        let mut automata = Automata1D::new(rule, -800, 1600);
+        automata.step(warmup);
+        group.bench_with_input(
+            BenchmarkId::new("infinite_rule4", warmup),
+            &warmup,
+            |b, _| b.iter(|| automata.step(100)),
+        );
+        // Rule 4 with 2 colors, stepped on packed words
+        let mut automata = Automata1D::new(Rule1D::new(2, 4), -800, 1600);
+        automata.step(warmup);
+        group.bench_with_input(
+            BenchmarkId::new("infinite_rule4_packed", warmup),
+            &warmup,
+            |b, _| b.iter(|| automata.step(100)),
+        );
+        let rule = Rule1D::new(3, 40327);
+        let mut automata =
+            Automata1D::with_boundary(rule, Boundary::Periodic, 1600, 0, 1600).unwrap();
+        automata.step(warmup);
+        group.bench_with_input(BenchmarkId::new("periodic_3c", warmup), &warmup, |b, _| {
+            b.iter(|| automata.step(100))
+        });
+    }
+    group.finish();
+}
+
+// 2 colors rules are stepped 64 cells at a time
+fn packed_benchmark(c: &mut Criterion) {
+    let mut group = c.benchmark_group("packed_step_100");
+    group.sample_size(10);
+    for &rule_nb in [30u64, 110].iter() {
+        let init = InitialCondition::Random {
+            density: 0.5,
+            seed: 0,
+        };
+        let rule = Rule1D::new(2, rule_nb);
+        let mut automata =
+            Automata1D::with_init(rule, Boundary::Periodic, 1_000_000, &init, 0, 80).unwrap();
+        group.bench_with_input(BenchmarkId::new("1M_cells", rule_nb), &rule_nb, |b, _| {
+            b.iter(|| automata.step(100))
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, step_benchmark, packed_benchmark);
+criterion_main!(benches);
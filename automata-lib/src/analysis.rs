@@ -0,0 +1,126 @@
+use crate::spacetime::SpaceTime;
+use std::collections::HashMap;
+
+// Measures of a run, entropies are in bits.
+// densities : ratio of each color on each step of the view, densities[step][color]
+// block_entropies : entropy of the blocks of 1, 2, ... consecutive cells of the view, over all the steps
+// temporal_entropies : entropy of the history of each position of the view, from x_start
+// active_widths : number of cells from the first to the last cell that differs from the background, on each step
+// spreading_rate : growth of the active width in cells per step, the slope of its least squares line
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct RunStats {
+    pub t_start: u64,
+    pub x_start: i64,
+    pub densities: Vec<Vec<f64>>,
+    pub block_entropies: Vec<f64>,
+    pub temporal_entropies: Vec<f64>,
+    pub active_widths: Vec<u64>,
+    pub spreading_rate: f64,
+}
+impl RunStats {
+    // Measures of the 'diagram' with colors below 'n_colors', with blocks up to 'max_block' cells.
+    // 'active_widths' are measured on the whole lattice by the automata, one per step of the diagram.
+    pub fn new(
+        diagram: &SpaceTime,
+        n_colors: u8,
+        max_block: usize,
+        active_widths: Vec<u64>,
+    ) -> RunStats {
+        RunStats {
+            t_start: diagram.t_start(),
+            x_start: diagram.x_start(),
+            densities: diagram
+                .rows()
+                .map(|row| color_densities(row, n_colors))
+                .collect(),
+            block_entropies: (1..=max_block)
+                .map(|size| block_entropy(diagram, size))
+                .collect(),
+            temporal_entropies: (0..diagram.width() as i64)
+                .map(|col| entropy(&diagram.column(diagram.x_start() + col).unwrap()))
+                .collect(),
+            spreading_rate: spreading_rate(&active_widths),
+            active_widths,
+        }
+    }
+}
+// Ratio of each color in 'cells'
+fn color_densities(cells: &[u8], n_colors: u8) -> Vec<f64> {
+    let mut counts = vec![0usize; n_colors as usize];
+    for &c in cells {
+        counts[c as usize] += 1;
+    }
+    counts
+        .into_iter()
+        .map(|count| count as f64 / cells.len().max(1) as f64)
+        .collect()
+}
+// Entropy of the blocks of 'size' consecutive cells in the rows of the diagram, blocks do not wrap
+fn block_entropy(diagram: &SpaceTime, size: usize) -> f64 {
+    let mut counts = HashMap::new();
+    for row in diagram.rows() {
+        for block in row.windows(size) {
+            *counts.entry(block).or_insert(0usize) += 1;
+        }
+    }
+    counts_entropy(counts.values().copied())
+}
+// Entropy of the colors in 'cells'
+fn entropy(cells: &[u8]) -> f64 {
+    let mut counts = [0usize; 256];
+    for &c in cells {
+        counts[c as usize] += 1;
+    }
+    counts_entropy(counts.iter().copied().filter(|&count| count > 0))
+}
+fn counts_entropy<I: Iterator<Item = usize> + Clone>(counts: I) -> f64 {
+    let total = counts.clone().sum::<usize>() as f64;
+    counts.fold(0.0, |entropy, count| {
+        let p = count as f64 / total;
+        entropy - p * p.log2()
+    })
+}
+fn spreading_rate(widths: &[u64]) -> f64 {
+    slope(&widths.iter().map(|&w| w as f64).collect::<Vec<_>>())
+}
+// Slope of the least squares line of the values, one per step, 0 with less than 2 steps
+pub(crate) fn slope(values: &[f64]) -> f64 {
+    let n = values.len() as f64;
+    if values.len() < 2 {
+        return 0.0;
+    }
+    let mean_t = (n - 1.0) / 2.0;
+    let mean_v = values.iter().sum::<f64>() / n;
+    let (cov, var) = values
+        .iter()
+        .enumerate()
+        .fold((0.0, 0.0), |(cov, var), (t, &v)| {
+            let dt = t as f64 - mean_t;
+            (cov + dt * (v - mean_v), var + dt * dt)
+        });
+    cov / var
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn run_stats_work() {
+        assert_eq!(color_densities(&[0, 1, 1, 2], 3), vec![0.25, 0.5, 0.25]);
+        assert_eq!(entropy(&[0, 0, 0]), 0.0);
+        assert_eq!(entropy(&[0, 1, 2, 3]), 2.0);
+        assert_eq!(spreading_rate(&[1, 3, 5, 7]), 2.0);
+        assert_eq!(spreading_rate(&[4]), 0.0);
+        // Alternating cells have 2 blocks of each size
+        let diagram = SpaceTime::from_cells(0, 0, 6, vec![0, 1, 0, 1, 0, 1, 1, 0, 1, 0, 1, 0]);
+        assert_eq!(block_entropy(&diagram, 1), 1.0);
+        assert_eq!(block_entropy(&diagram, 3), 1.0);
+        let stats = RunStats::new(&diagram, 2, 2, vec![6, 6]);
+        assert_eq!(stats.densities, vec![vec![0.5, 0.5], vec![0.5, 0.5]]);
+        assert_eq!(stats.block_entropies, vec![1.0, 1.0]);
+        assert_eq!(stats.temporal_entropies, vec![1.0; 6]);
+        assert_eq!(stats.spreading_rate, 0.0);
+    }
+}
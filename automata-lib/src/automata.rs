@@ -1,78 +1,523 @@
+use crate::analysis::RunStats;
+use crate::boundary::Boundary;
+use crate::cellular_rule::CellularRule;
+use crate::cycle::{least_rotation, rotation_period, Cycle};
+use crate::error::{Error, Result};
+use crate::init::InitialCondition;
+use crate::linear;
+use crate::packed::{self, PackedCells, PackedRule};
+use crate::preimage::Preimages;
 use crate::rules::*;
+use crate::spacetime::SpaceTime;
+use crate::update::{update_cells, UpdateScheme};
 use image::RgbImage;
+use num_bigint::BigUint;
+use rand::{Rng, SeedableRng};
+use rand_pcg::Pcg32;
+use std::borrow::Cow;
+use std::collections::hash_map::{Entry, HashMap};
+use std::convert::TryFrom;
 
-pub struct Automata1DIter<'a> {
-    automata: &'a Automata1D,
+pub struct Automata1DIter<'a, R: CellularRule> {
+    automata: &'a Automata1D<R>,
     idx: i32,
 }
-impl<'a> Iterator for Automata1DIter<'a> {
+impl<'a, R: CellularRule> Iterator for Automata1DIter<'a, R> {
     type Item = u8;
     fn next(&mut self) -> Option<Self::Item> {
         self.idx += 1;
         if self.idx >= self.automata.view_start + self.automata.view_width as i32 {
             None
-        } else if self.idx < self.automata.view_cell_start {
-            Some(*self.automata.cells.first().unwrap())
-        } else if self.idx < self.automata.view_cell_start + self.automata.cells.len() as i32 {
-            Some(
-                *self
-                    .automata
-                    .cells
-                    .get((self.idx - self.automata.view_cell_start) as usize)
-                    .unwrap(),
-            )
         } else {
-            Some(*self.automata.cells.last().unwrap())
+            Some(self.automata.get_cell(self.idx))
         }
     }
 }
-pub struct Automata1D {
-    rule: Rule1D,
+#[derive(Clone)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(
+        try_from = "Automata1DState<R>",
+        into = "Automata1DState<R>",
+        bound(serialize = "R: serde::Serialize + Clone")
+    )
+)]
+pub struct Automata1D<R: CellularRule = Rule1D> {
+    rule: R,
+    boundary: Boundary,
+    update: UpdateScheme,
     step: u32,
+    // Random generator of the new cells of stochastic rules
+    rng: Pcg32,
+    // Cells, unless they are kept in 'words'
     cells: Vec<u8>,
+    // Cells packed 64 per word between the steps of the packed rule, so that consecutive steps do not
+    // convert them, they are unpacked when the cells are changed otherwise
+    #[cfg_attr(feature = "serde", serde(skip))]
+    words: Option<PackedCells>,
+    // Scratch buffer reused by each step
+    #[cfg_attr(feature = "serde", serde(skip))]
+    next: Vec<u8>,
+    // Word parallel version of 2 colors rules
+    #[cfg_attr(feature = "serde", serde(skip))]
+    packed: Option<PackedRule>,
     view_start: i32,
     view_width: u32,
     view_cell_start: i32,
 }
-impl Automata1D {
-    pub fn new(rule: Rule1D, view_start: i32, view_width: u32) -> Automata1D {
+impl<R: CellularRule> Automata1D<R> {
+    // Automata on an infinite lattice, panics on a stochastic rule, try_new returns an Error instead
+    pub fn new(rule: R, view_start: i32, view_width: u32) -> Automata1D<R> {
+        Automata1D::try_new(rule, view_start, view_width).unwrap_or_else(|e| panic!("{}", e))
+    }
+    // Stochastic rules are not supported on an infinite lattice, the draws of the cells
+    // at its edges would be those of the whole background.
+    pub fn try_new(rule: R, view_start: i32, view_width: u32) -> Result<Automata1D<R>> {
+        if rule.is_stochastic() {
+            return Err(Error::UnsupportedBoundary(Boundary::Infinite));
+        }
         let cells = rule.initialize();
-        Automata1D {
+        let view_cell_start = -(cells.len() as i32 / 2);
+        let packed = PackedRule::new(&rule);
+        Ok(Automata1D {
             rule,
+            boundary: Boundary::Infinite,
+            update: UpdateScheme::Synchronous,
             step: 0,
+            rng: Pcg32::seed_from_u64(0),
             cells,
+            words: None,
+            next: Vec::new(),
+            packed,
             view_start,
             view_width,
-            view_cell_start: -3,
+            view_cell_start,
+        })
+    }
+    // Automata on a lattice of 'size' cells, numbered from 0, with a single seed cell at size/2.
+    // 'size' is ignored for an infinite boundary.
+    pub fn with_boundary(
+        rule: R,
+        boundary: Boundary,
+        size: u32,
+        view_start: i32,
+        view_width: u32,
+    ) -> Result<Automata1D<R>> {
+        Automata1D::with_init(
+            rule,
+            boundary,
+            size,
+            &InitialCondition::Seed,
+            view_start,
+            view_width,
+        )
+    }
+    // Automata on a lattice of 'size' cells, numbered from 0, starting from 'init'.
+    // On an infinite lattice, the 'size' initial cells are centered on cell 0, surrounded by background,
+    // and a seed is the rule own initialization. Stochastic rules need a finite lattice.
+    pub fn with_init(
+        rule: R,
+        boundary: Boundary,
+        size: u32,
+        init: &InitialCondition,
+        view_start: i32,
+        view_width: u32,
+    ) -> Result<Automata1D<R>> {
+        match boundary {
+            Boundary::Infinite if *init == InitialCondition::Seed || rule.is_stochastic() => {
+                return Automata1D::try_new(rule, view_start, view_width)
+            }
+            Boundary::Fixed(value) if value >= rule.get_n_colors() => {
+                return Err(Error::InvalidBoundaryValue(value))
+            }
+            _ if size == 0 => return Err(Error::InvalidSize(size)),
+            _ => (),
         }
+        let init_cells = init.cells(&rule, size)?;
+        let packed = PackedRule::new(&rule);
+        let (cells, view_cell_start) = if boundary.is_finite() {
+            (init_cells, 0)
+        } else {
+            let padding = 2 * rule.get_radius() as usize + 1;
+            let mut cells = vec![0; padding];
+            cells.extend(init_cells);
+            cells.extend(std::iter::repeat_n(0, padding));
+            (cells, -((padding + size as usize / 2) as i32))
+        };
+        Ok(Automata1D {
+            rule,
+            boundary,
+            update: UpdateScheme::Synchronous,
+            step: 0,
+            rng: Pcg32::seed_from_u64(0),
+            cells,
+            words: None,
+            next: Vec::new(),
+            packed,
+            view_start,
+            view_width,
+            view_cell_start,
+        })
     }
-    pub fn iter(&self) -> Automata1DIter {
+    pub fn iter(&self) -> Automata1DIter<'_, R> {
         Automata1DIter {
-            automata: &self,
+            automata: self,
             idx: self.view_start - 1,
         }
     }
+    // Value of the cell at position 'x', as seen from the rule.
+    pub fn get_cell(&self, x: i32) -> u8 {
+        let idx = x as i64 - self.view_cell_start as i64;
+        match &self.words {
+            Some(words) => self.boundary.get_with(words.len(), idx, |i| words.get(i)),
+            None => self.boundary.get_cell(&self.cells, idx),
+        }
+    }
+    // Stored cells, from the position of the first one
+    pub(crate) fn cells(&self) -> Cow<'_, [u8]> {
+        match &self.words {
+            Some(words) => {
+                let mut cells = Vec::new();
+                words.unpack(&mut cells);
+                Cow::Owned(cells)
+            }
+            None => Cow::Borrowed(&self.cells),
+        }
+    }
+    // Cells of the packed words back in bytes, keeping the background padding of an infinite lattice
+    fn unpack(&mut self) {
+        if let Some(words) = self.words.take() {
+            words.unpack(&mut self.cells);
+            if !self.boundary.is_finite() {
+                self.trim_background();
+            }
+        }
+    }
+    // Drop the background beyond the padding of 2*radius+1 cells on each side of an infinite lattice
+    fn trim_background(&mut self) {
+        let padding = 2 * self.rule.get_radius() as usize + 1;
+        let (skip_left, skip_right) = background_excess(&self.cells, padding);
+        self.cells.truncate(self.cells.len() - skip_right);
+        self.cells.drain(..skip_left);
+        self.view_cell_start += skip_left as i32;
+    }
+    // Set the cell at position 'x', an infinite lattice grows to contain it.
+    pub fn set_cell(&mut self, x: i32, value: u8) -> Result<()> {
+        if value >= self.rule.get_n_colors() {
+            return Err(Error::InvalidPattern(format!(
+                "{} is not a color of the rule",
+                value
+            )));
+        }
+        self.unpack();
+        let mut idx = x as i64 - self.view_cell_start as i64;
+        if self.boundary.is_finite() {
+            if idx < 0 || idx >= self.cells.len() as i64 {
+                return Err(Error::InvalidPattern(format!(
+                    "cell {} is outside of the lattice",
+                    x
+                )));
+            }
+        } else {
+            // Keep the background padding on both sides of the new cell
+            let padding = 2 * self.rule.get_radius() as i64 + 1;
+            let grow_left = (padding - idx).max(0) as usize;
+            let left = self.cells[0];
+            self.cells
+                .splice(0..0, std::iter::repeat_n(left, grow_left));
+            self.view_cell_start -= grow_left as i32;
+            idx += grow_left as i64;
+            let grow_right = (idx + padding + 1 - self.cells.len() as i64).max(0) as usize;
+            let right = self.cells[self.cells.len() - 1];
+            self.cells.extend(std::iter::repeat_n(right, grow_right));
+        }
+        self.cells[idx as usize] = value;
+        Ok(())
+    }
+    // Positions of the stored cells, from the first to after the last,
+    // beyond them cells come from the boundary.
+    pub(crate) fn cell_range(&self) -> (i64, i64) {
+        let start = self.view_cell_start as i64;
+        let len = self
+            .words
+            .as_ref()
+            .map_or(self.cells.len(), PackedCells::len);
+        (start, start + len as i64)
+    }
     pub fn step(&mut self, n_step: u32) {
-        self.cells.reserve(2 * n_step as usize);
+        if self.update != UpdateScheme::Synchronous {
+            self.step_update(n_step)
+        } else if self.rule.is_stochastic() {
+            self.step_random(n_step)
+        } else {
+            self.step_with(n_step, apply_windows::<R>, PackedRule::step_words)
+        }
+    }
+    // Restart the random generator of the new cells of stochastic rules, seeded with 0 on creation
+    pub fn set_seed(&mut self, seed: u64) {
+        self.rng = Pcg32::seed_from_u64(seed);
+    }
+    // Order of the updates of the cells of each step, only finite lattices support other schemes than synchronous
+    pub fn set_update_scheme(&mut self, update: UpdateScheme) -> Result<()> {
+        update.check()?;
+        if update != UpdateScheme::Synchronous && !self.boundary.is_finite() {
+            return Err(Error::UnsupportedBoundary(self.boundary));
+        }
+        self.update = update;
+        Ok(())
+    }
+    #[inline]
+    pub fn get_update_scheme(&self) -> UpdateScheme {
+        self.update
+    }
+    fn step_update(&mut self, n_step: u32) {
+        self.unpack();
         for _j in 0..n_step {
-            let cur_len = self.cells.len();
-            for i in 0..cur_len - 2 {
-                *self.cells.get_mut(i).unwrap() = self.rule.apply(&self.cells[i..i + 3]);
-            }
-            *self.cells.get_mut(cur_len - 2).unwrap() = self.cells[cur_len - 3];
-            *self.cells.get_mut(cur_len - 1).unwrap() = self.cells[cur_len - 3];
-            self.cells.insert(0, self.cells[0]);
-            self.cells.insert(0, self.cells[0]);
+            update_cells(
+                &self.rule,
+                self.boundary,
+                self.update,
+                &mut self.cells,
+                &mut self.next,
+                &mut self.rng,
+            );
             self.step += 1;
-            self.view_cell_start -= 1;
         }
     }
+    // New cells of stochastic rules are drawn from left to right with the random generator of the automata,
+    // their lattice is finite.
+    fn step_random(&mut self, n_step: u32) {
+        let mut rng = self.rng.clone();
+        self.step_finite(n_step, |rule: &R, src: &[u8], dst: &mut [u8]| {
+            let window = src.len() - dst.len() + 1;
+            for (cell, w) in dst.iter_mut().zip(src.windows(window)) {
+                *cell = rule.apply_random(w, rng.gen());
+            }
+        });
+        self.rng = rng;
+    }
+    fn step_with(&mut self, n_step: u32, apply: ApplyWindows<R>, step_words: packed::StepWords) {
+        if let (Some(packed), true) = (&self.packed, n_step > 0) {
+            let mut words = match self.words.take() {
+                Some(words) => words,
+                None => PackedCells::pack(&self.cells),
+            };
+            let shift = packed::step_packed(packed, &mut words, self.boundary, n_step, step_words);
+            self.words = Some(words);
+            self.view_cell_start += shift as i32;
+            self.step += n_step;
+        } else if self.boundary.is_finite() {
+            self.step_finite(n_step, apply)
+        } else {
+            self.step_infinite(n_step, apply)
+        }
+    }
+    fn step_finite<F: FnMut(&R, &[u8], &mut [u8])>(&mut self, n_step: u32, mut apply: F) {
+        self.unpack();
+        for _j in 0..n_step {
+            step_finite_cells(
+                &self.rule,
+                self.boundary,
+                &mut self.cells,
+                &mut self.next,
+                &mut apply,
+            );
+            self.step += 1;
+        }
+    }
+    // Each step the cells grows by 'radius' on each side.
+    // The edges of cells are always padded with at least 2*radius+1 background cells,
+    // so that the new edges are computed from background only.
+    // Background beyond this padding is dropped, so that a pattern that does not grow
+    // keeps a constant number of cells.
+    fn step_infinite<F: FnMut(&R, &[u8], &mut [u8])>(&mut self, n_step: u32, mut apply: F) {
+        self.unpack();
+        let radius = self.rule.get_radius() as usize;
+        let window = 2 * radius + 1;
+        for _j in 0..n_step {
+            let (skip_left, skip_right) = background_excess(&self.cells, window);
+            let cells = &self.cells[skip_left..self.cells.len() - skip_right];
+            // The next cells are computed in the second buffer, then both buffers are swapped
+            let next = &mut self.next;
+            next.clear();
+            next.resize(cells.len(), 0);
+            apply(&self.rule, cells, &mut next[2 * radius..]);
+            let left_edge = next[2 * radius];
+            let right_edge = next[next.len() - 1];
+            for cell in next[..2 * radius].iter_mut() {
+                *cell = left_edge;
+            }
+            next.extend(std::iter::repeat_n(right_edge, 2 * radius));
+            std::mem::swap(&mut self.cells, &mut self.next);
+            self.step += 1;
+            self.view_cell_start += skip_left as i32 - radius as i32;
+        }
+    }
+    // Go forward to 'step'.
+    // Linear rules jump there with their closed form when the boundary allows it,
+    // finite lattices being unfolded into rings, other rules and update schemes are stepped.
+    pub fn jump_to(&mut self, step: u32) -> Result<()> {
+        if step < self.step {
+            return Err(Error::StepInPast {
+                step,
+                current: self.step,
+            });
+        }
+        let n_step = step - self.step;
+        let coefs = match self.rule.linear_coefficients() {
+            Some(coefs) if n_step > 0 && self.update == UpdateScheme::Synchronous => coefs,
+            _ => {
+                self.step(n_step);
+                return Ok(());
+            }
+        };
+        self.unpack();
+        let n_colors = self.rule.get_n_colors();
+        let t = n_step as u64;
+        let symmetric = coefs.iter().eq(coefs.iter().rev());
+        let size = self.cells.len();
+        match self.boundary {
+            Boundary::Infinite if linear::is_prime(n_colors) => {
+                // The cells grow by 'radius' on the left at each step
+                let start = self.view_cell_start as i64 - t as i64 * self.rule.get_radius() as i64;
+                let start = i32::try_from(start).map_err(|_e| Error::PositionOutOfRange(start))?;
+                self.cells = linear::infinite_jump(&coefs, n_colors, &self.cells, t);
+                self.view_cell_start = start;
+                self.trim_background();
+            }
+            Boundary::Periodic => linear::ring_jump(&coefs, n_colors, &mut self.cells, t),
+            // Mirrored cells stay mirrored under a symmetric rule,
+            // which sees the mirror image at the edges like a reflective boundary
+            Boundary::Reflective if symmetric => {
+                let mut ring = self.cells.clone();
+                ring.extend(self.cells.iter().rev());
+                linear::ring_jump(&coefs, n_colors, &mut ring, t);
+                ring.truncate(size);
+                self.cells = ring;
+            }
+            // Cells mirrored with opposite values around two 0 cells stay so under a symmetric rule,
+            // and these cells stay 0 like a fixed boundary. With a larger radius, the cells next to the edges
+            // would read the mirrored cells beyond the 0 cells, they are stepped.
+            Boundary::Fixed(0) if symmetric && self.rule.get_radius() == 1 => {
+                let mut ring = self.cells.clone();
+                ring.push(0);
+                ring.extend(self.cells.iter().rev().map(|&c| (n_colors - c) % n_colors));
+                ring.push(0);
+                linear::ring_jump(&coefs, n_colors, &mut ring, t);
+                ring.truncate(size);
+                self.cells = ring;
+            }
+            _ => {
+                self.step(n_step);
+                return Ok(());
+            }
+        }
+        self.step = step;
+        Ok(())
+    }
+    // Cycle reached from the current step, None when the cells do not repeat within 'max_steps' steps.
+    // States are kept in a map, on a periodic lattice by their smallest rotation to find traveling cycles
+    // of synchronous updates.
+    // Only finite lattices and deterministic update schemes have cycles.
+    pub fn find_cycle(&self, max_steps: u32) -> Result<Option<Cycle>> {
+        if self.rule.is_stochastic() {
+            return Err(Error::StochasticRule);
+        }
+        if self.update.is_random() {
+            return Err(Error::UnsupportedUpdateScheme(self.update));
+        }
+        if !self.boundary.is_finite() {
+            return Err(Error::UnsupportedBoundary(self.boundary));
+        }
+        // Asynchronous updates start from the first cell, rotated cells do not have rotated futures
+        let periodic =
+            self.boundary == Boundary::Periodic && self.update == UpdateScheme::Synchronous;
+        // Step and rotation of each state seen
+        let mut seen = HashMap::new();
+        let mut cells = self.cells().into_owned();
+        let mut padded = Vec::new();
+        let mut rng = self.rng.clone();
+        for t in 0..=max_steps {
+            let rotation = if periodic { least_rotation(&cells) } else { 0 };
+            let mut key = cells.clone();
+            key.rotate_left(rotation);
+            match seen.entry(key) {
+                Entry::Occupied(e) => {
+                    let (t0, rotation0) = *e.get();
+                    // Cells are their key rotated right by their rotation,
+                    // the shift only matters up to the rotations that leave the cells unchanged.
+                    let d = rotation_period(e.key()) as i64;
+                    let mut shift = (rotation as i64 - rotation0 as i64).rem_euclid(d);
+                    if shift > d / 2 {
+                        shift -= d;
+                    }
+                    return Ok(Some(Cycle {
+                        transient: t0,
+                        period: t - t0,
+                        shift: shift as i32,
+                    }));
+                }
+                Entry::Vacant(e) => {
+                    e.insert((t, rotation));
+                }
+            }
+            match &self.packed {
+                _ if self.update != UpdateScheme::Synchronous => update_cells(
+                    &self.rule,
+                    self.boundary,
+                    self.update,
+                    &mut cells,
+                    &mut padded,
+                    &mut rng,
+                ),
+                Some(packed) => {
+                    let mut words = PackedCells::pack(&cells);
+                    packed::step_packed(
+                        packed,
+                        &mut words,
+                        self.boundary,
+                        1,
+                        PackedRule::step_words,
+                    );
+                    words.unpack(&mut cells);
+                }
+                None => step_finite_cells(
+                    &self.rule,
+                    self.boundary,
+                    &mut cells,
+                    &mut padded,
+                    apply_windows::<R>,
+                ),
+            }
+        }
+        Ok(None)
+    }
     #[inline]
     pub fn get_cur_step(&self) -> u32 {
         self.step
     }
+    // Number of stored cells, with the padding of background of an infinite lattice
     pub fn cells_len(&self) -> usize {
-        self.cells.len()
+        let cells = self.cells();
+        if self.boundary.is_finite() {
+            return cells.len();
+        }
+        let (skip_left, skip_right) =
+            background_excess(&cells, 2 * self.rule.get_radius() as usize + 1);
+        cells.len() - skip_left - skip_right
+    }
+    #[inline]
+    pub fn get_view_width(&self) -> u32 {
+        self.view_width
+    }
+    #[inline]
+    pub fn get_rule(&self) -> &R {
+        &self.rule
+    }
+    #[inline]
+    pub fn get_boundary(&self) -> Boundary {
+        self.boundary
     }
     pub fn as_text(&self) -> String {
         format!(
@@ -83,46 +528,319 @@ impl Automata1D {
                 .join("")
         )
     }
-    pub fn as_image_buffer(&mut self, n_step: u32) -> RgbImage {
-        let mut buf = Vec::new();
+    // Space-time diagram of the view, from the current step, then step 'n_step' times
+    pub fn space_time(&mut self, n_step: u32) -> SpaceTime {
+        let mut diagram = SpaceTime::new(self.view_start as i64, self.step as u64, self.view_width);
         for _i in 0..n_step {
-            buf.extend(
-                self.iter()
-                    .flat_map(|c| {
-                        let (r, g, b) = self.rule.cell_to_rgb(&c);
-                        vec![r, g, b]
-                    })
-                    .collect::<Vec<_>>(),
-            );
+            diagram.push_row(self.iter());
             self.step(1)
         }
-        RgbImage::from_raw(self.view_width, n_step, buf).unwrap()
+        diagram
     }
-    pub fn as_rgb_vec(&mut self, n_step: u32) -> Vec<(u8, u8, u8)> {
-        let mut buf = Vec::new();
+    // Positions of the first and last cells that differ from the background, None when there are none.
+    // On an infinite lattice the background is the cells beyond each edge, on a finite lattice it is color 0.
+    pub fn active_region(&self) -> Option<(i64, i64)> {
+        let cells = self.cells();
+        let (left, right) = if self.boundary.is_finite() {
+            (0, 0)
+        } else {
+            (cells[0], cells[cells.len() - 1])
+        };
+        let first = cells.iter().position(|&c| c != left)?;
+        let last = cells.iter().rposition(|&c| c != right)?;
+        if first > last {
+            return None;
+        }
+        let start = self.view_cell_start as i64;
+        Some((start + first as i64, start + last as i64))
+    }
+    // Measures of the view from the current step, then step 'n_step' times
+    pub fn run_stats(&mut self, n_step: u32, max_block: usize) -> RunStats {
+        self.space_time_with_stats(n_step, max_block).1
+    }
+    // Space-time diagram and measures of the view, from the current step, then step 'n_step' times
+    pub fn space_time_with_stats(
+        &mut self,
+        n_step: u32,
+        max_block: usize,
+    ) -> (SpaceTime, RunStats) {
+        let mut diagram = SpaceTime::new(self.view_start as i64, self.step as u64, self.view_width);
+        let mut active_widths = Vec::with_capacity(n_step as usize);
         for _i in 0..n_step {
-            buf.extend(
-                self.iter()
-                    .map(|c| self.rule.cell_to_rgb(&c))
-                    .collect::<Vec<_>>(),
-            );
+            diagram.push_row(self.iter());
+            active_widths.push(self.active_region().map_or(0, |(a, b)| (b - a + 1) as u64));
             self.step(1)
         }
-        buf
+        let stats = RunStats::new(&diagram, self.rule.get_n_colors(), max_block, active_widths);
+        (diagram, stats)
+    }
+    pub fn as_image_buffer(&mut self, n_step: u32) -> RgbImage {
+        self.space_time(n_step).to_image(&self.rule)
+    }
+    pub fn as_rgb_vec(&mut self, n_step: u32) -> Vec<(u8, u8, u8)> {
+        self.space_time(n_step).to_rgb_vec(&self.rule)
     }
     pub fn as_vec(&mut self, n_step: u32) -> Vec<u8> {
-        let mut buf = Vec::new();
-        for _i in 0..n_step {
-            buf.extend(self.iter().collect::<Vec<_>>());
-            self.step(1)
+        self.space_time(n_step).into_cells()
+    }
+}
+
+impl<R: CellularRule + Clone> Automata1D<R> {
+    // Automata at the previous step whose cells evolve to the current cells, the preimages,
+    // in lexicographic order of their cells.
+    // On an infinite lattice, preimages are the cells that only differ from their edge cells within the cells
+    // of the current step, narrowed by 'radius' on each side, the cells keeping one padding of background.
+    // Only the synchronous update scheme is supported.
+    pub fn preimages(&self) -> Result<impl Iterator<Item = Automata1D<R>> + '_> {
+        if self.step == 0 {
+            return Err(Error::StepBeforeStart {
+                n_step: 1,
+                current: 0,
+            });
+        }
+        let (search, cell_start) = self.preimage_search()?;
+        let radius = self.rule.get_radius() as usize;
+        Ok(search.map(move |cells| {
+            let mut automata = self.clone();
+            automata.words = None;
+            automata.step -= 1;
+            if self.boundary.is_finite() {
+                automata.cells = cells;
+            } else {
+                let padding = 2 * radius + 1;
+                let (left, right) = (cells[0], cells[cells.len() - 1]);
+                let mut padded = vec![left; padding];
+                padded.extend(cells);
+                padded.extend(std::iter::repeat_n(right, padding));
+                let (skip_left, skip_right) = background_excess(&padded, padding);
+                padded.truncate(padded.len() - skip_right);
+                padded.drain(..skip_left);
+                automata.cells = padded;
+                automata.view_cell_start = cell_start - padding as i32 + skip_left as i32;
+            }
+            automata
+        }))
+    }
+    // Number of preimages, as listed by preimages
+    pub fn count_preimages(&self) -> Result<BigUint> {
+        Ok(self.preimage_search()?.0.n_preimages())
+    }
+    // Search of the preimage cells, with the position of their first cell
+    fn preimage_search(&self) -> Result<(Preimages<'_, R>, i32)> {
+        if self.update != UpdateScheme::Synchronous {
+            return Err(Error::UnsupportedUpdateScheme(self.update));
+        }
+        let cells = self.cells();
+        if self.boundary.is_finite() {
+            let search = Preimages::new(&self.rule, self.boundary, &cells, 0, cells.len())?;
+            return Ok((search, 0));
         }
-        buf
+        // Target cells with one padding of background, the edges of the preimage cells are seen
+        // 'radius' cells beyond them on each side
+        let radius = self.rule.get_radius() as usize;
+        let (skip_left, skip_right) = background_excess(&cells, 2 * radius + 1);
+        let target = &cells[skip_left..cells.len() - skip_right];
+        let search = Preimages::new(
+            &self.rule,
+            self.boundary,
+            target,
+            -(radius as i64),
+            target.len() - 2 * radius,
+        )?;
+        Ok((search, self.view_cell_start + (skip_left + radius) as i32))
     }
 }
 
+// Serialized fields of Automata1D, with unpacked cells, checked and completed with the packed rule on deserialization
+#[cfg(feature = "serde")]
+#[derive(serde::Serialize, serde::Deserialize)]
+struct Automata1DState<R> {
+    rule: R,
+    boundary: Boundary,
+    update: UpdateScheme,
+    step: u32,
+    rng: Pcg32,
+    cells: Vec<u8>,
+    view_start: i32,
+    view_width: u32,
+    view_cell_start: i32,
+}
+#[cfg(feature = "serde")]
+impl<R: CellularRule> std::convert::TryFrom<Automata1DState<R>> for Automata1D<R> {
+    type Error = String;
+    fn try_from(state: Automata1DState<R>) -> std::result::Result<Automata1D<R>, String> {
+        let n_colors = state.rule.get_n_colors();
+        // An infinite lattice keeps its background padding on each side of at least one cell
+        let min_len = match state.boundary {
+            Boundary::Infinite => 2 * (2 * state.rule.get_radius() as usize + 1) + 1,
+            _ => 1,
+        };
+        if state.cells.len() < min_len || state.cells.iter().any(|&c| c >= n_colors) {
+            return Err(String::from("cells do not match the rule and boundary"));
+        }
+        if let Boundary::Fixed(value) = state.boundary {
+            if value >= n_colors {
+                return Err(Error::InvalidBoundaryValue(value).to_string());
+            }
+        }
+        if (state.update != UpdateScheme::Synchronous || state.rule.is_stochastic())
+            && !state.boundary.is_finite()
+        {
+            return Err(Error::UnsupportedBoundary(state.boundary).to_string());
+        }
+        state.update.check().map_err(|e| e.to_string())?;
+        let packed = PackedRule::new(&state.rule);
+        Ok(Automata1D {
+            rule: state.rule,
+            boundary: state.boundary,
+            update: state.update,
+            step: state.step,
+            rng: state.rng,
+            cells: state.cells,
+            words: None,
+            next: Vec::new(),
+            packed,
+            view_start: state.view_start,
+            view_width: state.view_width,
+            view_cell_start: state.view_cell_start,
+        })
+    }
+}
+#[cfg(feature = "serde")]
+impl<R: CellularRule> From<Automata1D<R>> for Automata1DState<R> {
+    fn from(mut automata: Automata1D<R>) -> Automata1DState<R> {
+        automata.unpack();
+        Automata1DState {
+            rule: automata.rule,
+            boundary: automata.boundary,
+            update: automata.update,
+            step: automata.step,
+            rng: automata.rng,
+            cells: automata.cells,
+            view_start: automata.view_start,
+            view_width: automata.view_width,
+            view_cell_start: automata.view_cell_start,
+        }
+    }
+}
+
+// Checkpoints start with this tag and a format version, followed by the bincode encoding of the automata
+#[cfg(feature = "serde")]
+const CHECKPOINT_MAGIC: &[u8; 4] = b"CA1D";
+#[cfg(feature = "serde")]
+const CHECKPOINT_VERSION: u8 = 3;
+
+#[cfg(feature = "serde")]
+impl<R> Automata1D<R>
+where
+    R: CellularRule + Clone + serde::Serialize + serde::de::DeserializeOwned,
+{
+    // Write the whole state, to resume the automata later with load_checkpoint
+    pub fn save_checkpoint<W: std::io::Write>(&self, mut writer: W) -> Result<()> {
+        writer.write_all(CHECKPOINT_MAGIC)?;
+        writer.write_all(&[CHECKPOINT_VERSION])?;
+        bincode::serialize_into(writer, self).map_err(|e| checkpoint_error(*e))
+    }
+    pub fn load_checkpoint<Rd: std::io::Read>(mut reader: Rd) -> Result<Automata1D<R>> {
+        let mut header = [0u8; 5];
+        reader.read_exact(&mut header)?;
+        if header[..4] != CHECKPOINT_MAGIC[..] {
+            return Err(Error::InvalidCheckpoint(String::from(
+                "not an automata checkpoint",
+            )));
+        }
+        if header[4] != CHECKPOINT_VERSION {
+            return Err(Error::InvalidCheckpoint(format!(
+                "unsupported version {}",
+                header[4]
+            )));
+        }
+        bincode::deserialize_from(reader).map_err(|e| checkpoint_error(*e))
+    }
+}
+// IO errors are kept as such, other bincode errors come from invalid content
+#[cfg(feature = "serde")]
+fn checkpoint_error(e: bincode::ErrorKind) -> Error {
+    match e {
+        bincode::ErrorKind::Io(e) => Error::Io(e),
+        e => Error::InvalidCheckpoint(e.to_string()),
+    }
+}
+
+#[cfg(feature = "parallel")]
+impl<R: CellularRule + Sync> Automata1D<R> {
+    // Same as step, with the cells split in chunks computed by the threads of the rayon pool.
+    // Each chunk reads its own copy of the 'radius' cells around it, the result is identical.
+    // Stochastic rules and update schemes other than synchronous are stepped sequentially, like step.
+    pub fn step_parallel(&mut self, n_step: u32) {
+        if self.rule.is_stochastic() || self.update != UpdateScheme::Synchronous {
+            return self.step(n_step);
+        }
+        self.step_with(n_step, par_apply_windows::<R>, PackedRule::par_step_words)
+    }
+}
+
+// Compute each cell of 'dst' from its window in 'src', 'src' has 'radius' more cells on each side
+type ApplyWindows<R> = fn(&R, &[u8], &mut [u8]);
+pub(crate) fn apply_windows<R: CellularRule>(rule: &R, src: &[u8], dst: &mut [u8]) {
+    let window = src.len() - dst.len() + 1;
+    for (cell, w) in dst.iter_mut().zip(src.windows(window)) {
+        *cell = rule.apply(w);
+    }
+}
+#[cfg(feature = "parallel")]
+fn par_apply_windows<R: CellularRule + Sync>(rule: &R, src: &[u8], dst: &mut [u8]) {
+    use rayon::prelude::*;
+    let halo = src.len() - dst.len();
+    dst.par_chunks_mut(PAR_CHUNK_CELLS)
+        .enumerate()
+        .for_each(|(k, chunk)| {
+            let start = k * PAR_CHUNK_CELLS;
+            apply_windows(rule, &src[start..start + chunk.len() + halo], chunk)
+        });
+}
+// Number of cells computed by each parallel task, tests use small chunks to check their edges
+#[cfg(feature = "parallel")]
+const PAR_CHUNK_CELLS: usize = if cfg!(test) { 100 } else { 1 << 14 };
+
+// One step of the cells of a finite lattice, 'padded' is a scratch buffer
+pub(crate) fn step_finite_cells<R: CellularRule, F: FnMut(&R, &[u8], &mut [u8])>(
+    rule: &R,
+    boundary: Boundary,
+    cells: &mut [u8],
+    padded: &mut Vec<u8>,
+    mut apply: F,
+) {
+    let radius = rule.get_radius() as i64;
+    let size = cells.len() as i64;
+    padded.clear();
+    padded.extend((-radius..0).map(|i| boundary.get_cell(cells, i)));
+    padded.extend_from_slice(cells);
+    padded.extend((size..size + radius).map(|i| boundary.get_cell(cells, i)));
+    apply(rule, padded, cells);
+}
+// Number of background cells that can be dropped on the left and on the right of 'cells',
+// keeping 'padding' cells of background on each side.
+fn background_excess(cells: &[u8], padding: usize) -> (usize, usize) {
+    let len = cells.len();
+    let left = cells.iter().take_while(|&&c| c == cells[0]).count();
+    if left == len {
+        // Uniform cells, only keep the padding on each side of a single cell
+        return (len.saturating_sub(2 * padding + 1), 0);
+    }
+    let right = cells
+        .iter()
+        .rev()
+        .take_while(|&&c| c == cells[len - 1])
+        .count();
+    (left.saturating_sub(padding), right.saturating_sub(padding))
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::cellular_rule::FnRule;
+    use num_bigint::BigUint;
 
     #[test]
     fn automata_1d_works() {
@@ -178,6 +896,18 @@ mod tests {
         }
     }
     #[test]
+    fn automata_1d_background_is_dropped() {
+        // Rule 4 keeps the seed unchanged, rule 127 alternates uniform black and white
+        let mut automata = Automata1D::new(Rule1D::new(2, 4), -5, 11);
+        let mut blinking = Automata1D::new(Rule1D::new(2, 127), -5, 11);
+        automata.step(1000);
+        blinking.step(1000);
+        assert_eq!(automata.as_text(), "|     *     |");
+        assert_eq!(blinking.as_text(), "|           |");
+        assert!(automata.cells_len() <= 11);
+        assert!(blinking.cells_len() <= 11);
+    }
+    #[test]
     fn automata_1d_iter_works() {
         // Rule 254 create black cell whenever any cell was black
         let rule = Rule1D::new(2, 254);
@@ -196,4 +926,510 @@ mod tests {
             vec![0, 0, 0, 0, 1, 1, 1, 0, 0, 0, 0]
         );
     }
+    #[test]
+    fn automata_1d_custom_rule_works() {
+        // Rule 30 as a closure, left XOR (center OR right)
+        let rule = FnRule::new(2, 1, |cells: &[u8]| cells[0] ^ (cells[1] | cells[2]));
+        let mut automata = Automata1D::new(rule, -20, 41);
+        let mut reference = Automata1D::new(Rule1D::new(2, 30), -20, 41);
+        for _i in 0..30 {
+            assert_eq!(automata.as_text(), reference.as_text());
+            automata.step(1);
+            reference.step(1);
+        }
+    }
+    #[test]
+    fn automata_1d_periodic_works() {
+        // Rule 170 shifts the cells to the left, the seed wraps around the ring
+        let rule = Rule1D::new(2, 170);
+        let mut automata = Automata1D::with_boundary(rule, Boundary::Periodic, 5, 0, 5).unwrap();
+        assert_eq!(automata.as_text(), "|  *  |");
+        automata.step(1);
+        assert_eq!(automata.as_text(), "| *   |");
+        automata.step(2);
+        assert_eq!(automata.as_text(), "|    *|");
+        automata.step(2);
+        assert_eq!(automata.as_text(), "|  *  |");
+        assert_eq!(automata.cells_len(), 5);
+        // View outside of the ring sees the ring repeated
+        let automata = Automata1D::with_boundary(Rule1D::new(2, 0), Boundary::Periodic, 3, -3, 9);
+        assert_eq!(automata.unwrap().as_text(), "| *  *  * |");
+    }
+    #[test]
+    fn automata_1d_fixed_and_reflective_works() {
+        // Rule 254, black spreads to the borders, fixed black borders spread inward
+        let rule = Rule1D::new(2, 254);
+        let mut automata = Automata1D::with_boundary(rule, Boundary::Fixed(1), 7, 0, 7).unwrap();
+        assert_eq!(automata.as_text(), "|   *   |");
+        automata.step(1);
+        assert_eq!(automata.as_text(), "|* *** *|");
+        automata.step(1);
+        assert_eq!(automata.as_text(), "|*******|");
+        // Rule 170 shifts the cells to the left, the seed leaves the lattice, the right border mirrors white cells
+        let rule = Rule1D::new(2, 170);
+        let mut automata = Automata1D::with_boundary(rule, Boundary::Reflective, 3, 0, 3).unwrap();
+        automata.step(1);
+        assert_eq!(automata.as_text(), "|*  |");
+        automata.step(1);
+        assert_eq!(automata.as_text(), "|   |");
+        assert!(matches!(
+            Automata1D::with_boundary(Rule1D::new(2, 0), Boundary::Fixed(2), 3, 0, 3),
+            Err(Error::InvalidBoundaryValue(2))
+        ));
+        assert!(matches!(
+            Automata1D::with_boundary(Rule1D::new(2, 0), Boundary::Periodic, 0, 0, 3),
+            Err(Error::InvalidSize(0))
+        ));
+    }
+    #[test]
+    fn automata_1d_init_works() {
+        // Initial cells are centered on cell 0 of an infinite lattice
+        let init = InitialCondition::Pattern(vec![1, 2, 0, 1]);
+        let automata =
+            Automata1D::with_init(Rule1D::new(3, 0), Boundary::Infinite, 4, &init, -4, 8);
+        assert_eq!(automata.unwrap().as_text(), "|  +* +  |");
+        // Rule 204 is the identity
+        let init = InitialCondition::Defect {
+            background: vec![0, 1],
+            defect: vec![1],
+        };
+        let rule = Rule1D::new(2, 204);
+        let mut automata = Automata1D::with_init(rule, Boundary::Periodic, 6, &init, 0, 6).unwrap();
+        assert_eq!(automata.as_text(), "| *** *|");
+        automata.step(3);
+        assert_eq!(automata.as_text(), "| *** *|");
+        // An infinite lattice matches a large enough fixed lattice with a background border
+        let init = InitialCondition::Random {
+            density: 0.5,
+            seed: 3,
+        };
+        let rule = Rule1D::new(2, 30);
+        let mut automata =
+            Automata1D::with_init(rule, Boundary::Infinite, 20, &init, -30, 60).unwrap();
+        let init = InitialCondition::Pattern(init.cells(&Rule1D::new(2, 30), 20).unwrap());
+        let rule = Rule1D::new(2, 30);
+        let mut reference =
+            Automata1D::with_init(rule, Boundary::Fixed(0), 100, &init, 20, 60).unwrap();
+        for _i in 0..15 {
+            assert_eq!(automata.as_text(), reference.as_text());
+            automata.step(1);
+            reference.step(1);
+        }
+    }
+    #[test]
+    fn automata_1d_packed_matches_bytes() {
+        // Same evolution with the packed backend and with one byte per cell
+        let check = |radius: u8, rule_nb: u128, boundary: Boundary, size, init| {
+            let rule = Rule1D::with_radius(2, radius, &BigUint::from(rule_nb));
+            let mut bytes = Automata1D::with_init(rule, boundary, size, init, -150, 300).unwrap();
+            assert!(bytes.packed.is_some());
+            bytes.packed = None;
+            let rule = Rule1D::with_radius(2, radius, &BigUint::from(rule_nb));
+            let mut packed = Automata1D::with_init(rule, boundary, size, init, -150, 300).unwrap();
+            for n_step in [1, 7, 64].iter() {
+                bytes.step(*n_step);
+                packed.step(*n_step);
+                assert_eq!(packed.as_text(), bytes.as_text());
+                assert_eq!(packed.get_cur_step(), bytes.get_cur_step());
+                assert_eq!(packed.active_region(), bytes.active_region());
+                // The cells stay packed between the steps
+                assert!(packed.words.is_some());
+            }
+            packed.set_cell(0, 1).unwrap();
+            bytes.set_cell(0, 1).unwrap();
+            assert!(packed.words.is_none());
+            packed.step(3);
+            bytes.step(3);
+            assert_eq!(packed.as_text(), bytes.as_text());
+        };
+        let init = InitialCondition::Random {
+            density: 0.5,
+            seed: 1,
+        };
+        let boundaries = [
+            Boundary::Infinite,
+            Boundary::Periodic,
+            Boundary::Fixed(1),
+            Boundary::Reflective,
+        ];
+        for rule_nb in 0..256 {
+            for boundary in boundaries.iter() {
+                for size in [1, 63, 200].iter() {
+                    check(1, rule_nb, *boundary, *size, &init);
+                }
+            }
+            check(1, rule_nb, Boundary::Infinite, 1, &InitialCondition::Seed);
+        }
+        for rule_nb in [1436965290, 0xdead_beef, 0x7fff_fffe].iter() {
+            for boundary in boundaries.iter() {
+                check(2, *rule_nb, *boundary, 130, &init);
+            }
+        }
+        check(3, u128::MAX / 3, Boundary::Periodic, 130, &init);
+    }
+    #[cfg(feature = "parallel")]
+    #[test]
+    fn automata_1d_parallel_matches_sequential() {
+        let init = InitialCondition::Random {
+            density: 0.5,
+            seed: 2,
+        };
+        let boundaries = [
+            Boundary::Infinite,
+            Boundary::Periodic,
+            Boundary::Fixed(1),
+            Boundary::Reflective,
+        ];
+        let check = |n_colors: u8, rule_nb: u64, packed: bool| {
+            for boundary in boundaries.iter() {
+                let build = || {
+                    let rule = Rule1D::new(n_colors, rule_nb);
+                    let mut automata =
+                        Automata1D::with_init(rule, *boundary, 1000, &init, 0, 10).unwrap();
+                    if !packed {
+                        automata.packed = None;
+                    }
+                    automata
+                };
+                let mut sequential = build();
+                let mut parallel = build();
+                for _i in 0..3 {
+                    sequential.step(5);
+                    parallel.step_parallel(5);
+                    assert_eq!(parallel.cells(), sequential.cells());
+                    assert_eq!(parallel.view_cell_start, sequential.view_cell_start);
+                }
+            }
+        };
+        for rule_nb in 0..256 {
+            check(2, rule_nb, true);
+            check(2, rule_nb, false);
+        }
+        for rule_nb in (0..59049).step_by(997) {
+            check(3, rule_nb, false);
+        }
+        for rule_nb in (0..1 << 20).step_by(17_389) {
+            check(4, rule_nb, false);
+        }
+    }
+    #[test]
+    fn automata_1d_jump_matches_step() {
+        let check = |rule: &dyn Fn() -> Rule1D, boundary: Boundary, size: u32| {
+            assert!(rule().linear_coefficients().is_some());
+            let init = InitialCondition::Random {
+                density: 0.5,
+                seed: size as u64,
+            };
+            let build = || Automata1D::with_init(rule(), boundary, size, &init, -150, 300).unwrap();
+            let mut stepped = build();
+            let mut jumped = build();
+            for &target in &[1, 2, 7, 8, 30, 81, 100] {
+                stepped.step(target - stepped.get_cur_step());
+                jumped.jump_to(target).unwrap();
+                assert_eq!(jumped.get_cur_step(), target);
+                assert_eq!(jumped.as_text(), stepped.as_text());
+            }
+            let mut direct = build();
+            direct.jump_to(100).unwrap();
+            assert_eq!(direct.as_text(), stepped.as_text());
+        };
+        let boundaries = [
+            Boundary::Infinite,
+            Boundary::Periodic,
+            Boundary::Reflective,
+            Boundary::Fixed(0),
+            Boundary::Fixed(1),
+        ];
+        for &rule_nb in &[90, 150, 60, 102, 0, 204] {
+            for &boundary in &boundaries {
+                check(&|| Rule1D::new(2, rule_nb), boundary, 37);
+                check(&|| Rule1D::new(2, rule_nb), boundary, 1);
+            }
+        }
+        // Totalistic rules whose output is the sum modulo a prime and a composite number of colors
+        for &n_colors in &[3u8, 4] {
+            let k = n_colors as u32;
+            let rule_nb = (0..3 * (k - 1) + 1)
+                .map(|s| BigUint::from(s % k) * num_traits::pow(BigUint::from(k), s as usize))
+                .sum::<BigUint>();
+            let rule = || Rule1D::with_kind(RuleKind::Totalistic, n_colors, 1, &rule_nb);
+            for &boundary in &boundaries[..4] {
+                check(&rule, boundary, 20);
+            }
+        }
+        // Sum of the edge cells of the window, modulo 2, with radius 2 and 3
+        for &radius in &[2u8, 3] {
+            let window = 2 * radius as u32 + 1;
+            let rule_nb = (0..1u32 << window)
+                .filter(|w| (w ^ (w >> (window - 1))) & 1 == 1)
+                .map(|w| BigUint::from(1u32) << w as usize)
+                .sum::<BigUint>();
+            let rule = || Rule1D::with_radius(2, radius, &rule_nb);
+            for &boundary in &boundaries {
+                check(&rule, boundary, 9);
+                check(&rule, boundary, 37);
+            }
+        }
+        // Other rules are stepped
+        let mut automata = Automata1D::new(Rule1D::new(2, 30), -20, 40);
+        assert!(Rule1D::new(2, 30).linear_coefficients().is_none());
+        automata.jump_to(12).unwrap();
+        let mut reference = Automata1D::new(Rule1D::new(2, 30), -20, 40);
+        reference.step(12);
+        assert_eq!(automata.as_text(), reference.as_text());
+        assert!(automata.jump_to(11).is_err());
+        // A far jump only costs a few steps per base 2 digit,
+        // rule 90 clears any ring of 64 cells after 32 steps
+        let init = InitialCondition::Random {
+            density: 0.5,
+            seed: 1,
+        };
+        let rule = Rule1D::new(2, 90);
+        let mut automata =
+            Automata1D::with_init(rule, Boundary::Periodic, 64, &init, 0, 64).unwrap();
+        automata.jump_to(u32::MAX).unwrap();
+        assert_eq!(automata.get_cur_step(), u32::MAX);
+        assert!(automata.iter().all(|c| c == 0));
+        // Cells of an infinite lattice would grow beyond the range of the positions
+        let mut automata = Automata1D::new(
+            Rule1D::with_radius(2, 2, &BigUint::from(0x96696996u32)),
+            0,
+            10,
+        );
+        assert!(matches!(
+            automata.jump_to(u32::MAX),
+            Err(Error::PositionOutOfRange(_))
+        ));
+        assert_eq!(automata.get_cur_step(), 0);
+    }
+    #[cfg(feature = "serde")]
+    #[test]
+    fn automata_1d_checkpoint_works() {
+        let init = InitialCondition::Random {
+            density: 0.5,
+            seed: 5,
+        };
+        for &(n_colors, rule_nb) in &[(2, 110), (3, 40327)] {
+            for &boundary in &[Boundary::Infinite, Boundary::Periodic, Boundary::Fixed(1)] {
+                let rule = Rule1D::new(n_colors, rule_nb);
+                let mut automata =
+                    Automata1D::with_init(rule, boundary, 50, &init, -40, 80).unwrap();
+                automata.step(17);
+                let mut checkpoint = Vec::new();
+                automata.save_checkpoint(&mut checkpoint).unwrap();
+                let mut resumed: Automata1D = Automata1D::load_checkpoint(&checkpoint[..]).unwrap();
+                assert_eq!(resumed.get_cur_step(), 17);
+                assert_eq!(resumed.packed.is_some(), n_colors == 2);
+                automata.step(20);
+                resumed.step(20);
+                assert_eq!(resumed.as_text(), automata.as_text());
+                // Same cells, whether they were kept packed or not
+                let (mut a, mut b) = (Vec::new(), Vec::new());
+                resumed.save_checkpoint(&mut a).unwrap();
+                automata.save_checkpoint(&mut b).unwrap();
+                assert_eq!(a, b);
+            }
+        }
+        let mut checkpoint = Vec::new();
+        let automata = Automata1D::new(Rule1D::new(3, 1234), -5, 10);
+        automata.save_checkpoint(&mut checkpoint).unwrap();
+        let load = |bytes: &[u8]| Automata1D::<Rule1D>::load_checkpoint(bytes);
+        assert!(matches!(
+            load(b"PNG\0\0\0"),
+            Err(Error::InvalidCheckpoint(_))
+        ));
+        assert!(matches!(load(&checkpoint[..20]), Err(Error::Io(_))));
+        // The last cell is set to a color that the rule does not have
+        let mut invalid = checkpoint.clone();
+        let len = invalid.len();
+        invalid[len - 13] = 3;
+        assert!(matches!(load(&invalid), Err(Error::InvalidCheckpoint(_))));
+    }
+    #[test]
+    fn automata_1d_run_stats_works() {
+        // Rule 30 grows by one cell on each side per step
+        let mut automata = Automata1D::new(Rule1D::new(2, 30), -20, 41);
+        assert_eq!(automata.active_region(), Some((0, 0)));
+        let stats = automata.run_stats(20, 4);
+        assert_eq!(automata.active_region(), Some((-20, 20)));
+        assert_eq!(stats.active_widths[..3], [1, 3, 5]);
+        assert_eq!(stats.spreading_rate, 2.0);
+        assert_eq!((stats.t_start, stats.x_start), (0, -20));
+        assert_eq!(stats.densities[0], vec![40.0 / 41.0, 1.0 / 41.0]);
+        assert_eq!(stats.block_entropies.len(), 4);
+        assert_eq!(stats.temporal_entropies.len(), 41);
+        // The left edge cell is only reached after the measured steps, the center cell is mixed
+        assert_eq!(stats.temporal_entropies[0], 0.0);
+        assert!(stats.temporal_entropies[20] > 0.5);
+        // Rule 0 clears everything
+        let mut automata = Automata1D::new(Rule1D::new(2, 0), 0, 10);
+        automata.step(1);
+        assert_eq!(automata.active_region(), None);
+        let mut automata =
+            Automata1D::with_boundary(Rule1D::new(2, 204), Boundary::Periodic, 10, 0, 10).unwrap();
+        assert_eq!(automata.run_stats(5, 1).active_widths, vec![1; 5]);
+    }
+    #[test]
+    fn automata_1d_find_cycle_works() {
+        let init = InitialCondition::Pattern(vec![1, 1, 0, 1, 0, 0, 0]);
+        let cycle = |rule_nb: u64, boundary: Boundary| {
+            let automata = Automata1D::with_init(Rule1D::new(2, rule_nb), boundary, 7, &init, 0, 7);
+            automata.unwrap().find_cycle(1000).unwrap().unwrap()
+        };
+        let still = |transient| Cycle {
+            transient,
+            period: 1,
+            shift: 0,
+        };
+        // Rule 204 is the identity, rule 170 moves the cells left and rule 240 right
+        assert_eq!(cycle(204, Boundary::Periodic), still(0));
+        let traveling = |shift| Cycle {
+            transient: 0,
+            period: 1,
+            shift,
+        };
+        assert_eq!(cycle(170, Boundary::Periodic), traveling(-1));
+        assert_eq!(cycle(240, Boundary::Periodic), traveling(1));
+        // On a fixed lattice, the cells leave through the left edge
+        assert_eq!(cycle(170, Boundary::Fixed(0)), still(4));
+        assert!(Automata1D::new(Rule1D::new(2, 30), 0, 1)
+            .find_cycle(10)
+            .is_err());
+        let automata = Automata1D::with_boundary(Rule1D::new(2, 30), Boundary::Periodic, 40, 0, 1);
+        assert_eq!(automata.unwrap().find_cycle(10).unwrap(), None);
+        // The cycle matches the first exact repeat of the cells, once the shift is repeated enough
+        // times to come back to the same cells
+        for rule_nb in 0..256 {
+            for &boundary in &[Boundary::Periodic, Boundary::Reflective, Boundary::Fixed(1)] {
+                for size in 1..12 {
+                    let init = InitialCondition::Random {
+                        density: 0.5,
+                        seed: size as u64,
+                    };
+                    let rule = Rule1D::new(2, rule_nb);
+                    let mut automata =
+                        Automata1D::with_init(rule, boundary, size, &init, 0, size).unwrap();
+                    let cycle = automata.find_cycle(5000).unwrap().unwrap();
+                    let mut seen = HashMap::new();
+                    while !seen.contains_key(&*automata.cells()) {
+                        seen.insert(automata.cells().into_owned(), automata.step);
+                        automata.step(1);
+                    }
+                    let transient = seen[&*automata.cells()];
+                    let period = automata.step - transient;
+                    assert_eq!(cycle.transient, transient);
+                    let mut moved = automata.cells().into_owned();
+                    moved.rotate_right(cycle.shift.rem_euclid(size as i32) as usize);
+                    automata.step(cycle.period);
+                    assert_eq!(automata.cells(), moved);
+                    assert_eq!(period % cycle.period, 0);
+                    if cycle.shift == 0 {
+                        assert_eq!(period, cycle.period);
+                    }
+                }
+            }
+        }
+    }
+    #[test]
+    fn automata_1d_radius_works() {
+        // Radius 2, output is black whenever any cell of the window was black
+        let rule = Rule1D::with_radius(2, 2, &BigUint::from(u32::MAX - 1));
+        let mut automata = Automata1D::new(rule, -6, 13);
+        assert_eq!(automata.as_text(), "|      *      |");
+        automata.step(1);
+        assert_eq!(automata.as_text(), "|    *****    |");
+        automata.step(1);
+        assert_eq!(automata.as_text(), "|  *********  |");
+        automata.step(1);
+        assert_eq!(automata.as_text(), "|*************|");
+        assert_eq!(automata.cells_len(), 11 + 3 * 4);
+    }
+    #[test]
+    fn automata_1d_preimages_works() {
+        // Preimages on finite lattices match the cells whose next step is the target, in lexicographic order
+        for &boundary in &[
+            Boundary::Periodic,
+            Boundary::Fixed(0),
+            Boundary::Fixed(1),
+            Boundary::Reflective,
+        ] {
+            let rules = [0, 30, 90, 110]
+                .iter()
+                .map(|&nb| Rule1D::new(2, nb))
+                .chain(Some(Rule1D::with_radius(
+                    2,
+                    2,
+                    &BigUint::from(1234567891u32),
+                )));
+            for rule in rules {
+                for size in 1..=6u32 {
+                    let mut expected = HashMap::new();
+                    for n in 0..1u32 << size {
+                        let cells = (0..size).map(|i| (n >> (size - 1 - i) & 1) as u8);
+                        let mut automata =
+                            Automata1D::with_boundary(rule.clone(), boundary, size, 0, size)
+                                .unwrap();
+                        automata.cells = cells.collect();
+                        let preimage = automata.cells().into_owned();
+                        automata.step(1);
+                        expected
+                            .entry(automata.cells().into_owned())
+                            .or_insert_with(Vec::new)
+                            .push(preimage);
+                    }
+                    for n in 0..1u32 << size {
+                        let mut automata =
+                            Automata1D::with_boundary(rule.clone(), boundary, size, 0, size)
+                                .unwrap();
+                        automata.step(1);
+                        automata.unpack();
+                        automata.cells = (0..size).map(|i| (n >> i & 1) as u8).collect();
+                        let preimages = automata
+                            .preimages()
+                            .unwrap()
+                            .map(|preimage| {
+                                assert_eq!(preimage.get_cur_step(), 0);
+                                preimage.cells
+                            })
+                            .collect::<Vec<_>>();
+                        let expected = expected.remove(&*automata.cells()).unwrap_or_default();
+                        assert_eq!(preimages, expected);
+                        assert_eq!(
+                            automata.count_preimages().unwrap(),
+                            BigUint::from(expected.len())
+                        );
+                    }
+                }
+            }
+        }
+        // On an infinite lattice, preimages evolve to the cells, among them the previous step
+        let mut automata = Automata1D::new(Rule1D::new(2, 110), -10, 21);
+        automata.step(6);
+        let previous = {
+            let mut automata = Automata1D::new(Rule1D::new(2, 110), -10, 21);
+            automata.step(5);
+            automata
+        };
+        let mut found = false;
+        for mut preimage in automata.preimages().unwrap() {
+            assert_eq!(preimage.get_cur_step(), 5);
+            found |= (-30..30).all(|x| preimage.get_cell(x) == previous.get_cell(x));
+            preimage.step(1);
+            assert!((-30..30).all(|x| preimage.get_cell(x) == automata.get_cell(x)));
+        }
+        assert!(found);
+        // Rule 0 has no preimage of a black cell, and 2^6 of white cells on a ring of 6 cells
+        let mut automata = Automata1D::new(Rule1D::new(2, 0), -5, 11);
+        assert!(matches!(
+            automata.preimages(),
+            Err(Error::StepBeforeStart { .. })
+        ));
+        automata.step(1);
+        automata.set_cell(0, 1).unwrap();
+        assert_eq!(automata.count_preimages().unwrap(), BigUint::from(0u32));
+        let mut automata =
+            Automata1D::with_boundary(Rule1D::new(2, 0), Boundary::Periodic, 6, 0, 6).unwrap();
+        automata.step(1);
+        assert_eq!(automata.count_preimages().unwrap(), BigUint::from(64u32));
+    }
 }
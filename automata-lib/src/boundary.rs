@@ -0,0 +1,150 @@
+use std::fmt;
+use std::str::FromStr;
+
+// How the cells beyond the edges of the lattice are seen by the rule.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Boundary {
+    // Unbounded lattice, grows by 'radius' cells on each side at each step.
+    // Cells beyond the edges have the value of the edge cells.
+    Infinite,
+    // Finite ring, the first cell is the right neighbor of the last cell
+    Periodic,
+    // Finite lattice, cells beyond the edges have the provided value
+    Fixed(u8),
+    // Finite lattice, cells beyond the edges mirror the lattice, cell -1 is cell 0, cell -2 is cell 1, ...
+    Reflective,
+}
+impl Boundary {
+    #[inline]
+    pub fn is_finite(self) -> bool {
+        self != Boundary::Infinite
+    }
+    // Value of the cell at position 'idx' of a finite lattice of 'cells', 'idx' can be outside of the lattice.
+    #[inline]
+    pub(crate) fn get_cell(self, cells: &[u8], idx: i64) -> u8 {
+        self.get_with(cells.len(), idx, |i| cells[i])
+    }
+    // Same as get_cell, for a lattice of 'size' cells whose cell 'i' is read with 'cell(i)'
+    #[inline]
+    pub(crate) fn get_with<F: Fn(usize) -> u8>(self, size: usize, idx: i64, cell: F) -> u8 {
+        let size = size as i64;
+        if idx >= 0 && idx < size {
+            return cell(idx as usize);
+        }
+        match self {
+            Boundary::Infinite => cell(idx.clamp(0, size - 1) as usize),
+            Boundary::Periodic => cell(idx.rem_euclid(size) as usize),
+            Boundary::Fixed(value) => value,
+            Boundary::Reflective => {
+                let folded = idx.rem_euclid(2 * size);
+                if folded < size {
+                    cell(folded as usize)
+                } else {
+                    cell((2 * size - 1 - folded) as usize)
+                }
+            }
+        }
+    }
+    // Index of the cell at position 'idx' in a finite lattice of 'size' cells,
+    // None beyond the edges of a fixed boundary, where cells have the boundary value.
+    pub(crate) fn index(self, size: usize, idx: i64) -> Option<usize> {
+        let size = size as i64;
+        if idx >= 0 && idx < size {
+            return Some(idx as usize);
+        }
+        match self {
+            Boundary::Infinite => Some(idx.clamp(0, size - 1) as usize),
+            Boundary::Periodic => Some(idx.rem_euclid(size) as usize),
+            Boundary::Fixed(_) => None,
+            Boundary::Reflective => {
+                let folded = idx.rem_euclid(2 * size);
+                Some(if folded < size {
+                    folded
+                } else {
+                    2 * size - 1 - folded
+                } as usize)
+            }
+        }
+    }
+}
+impl fmt::Display for Boundary {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Boundary::Infinite => write!(f, "infinite"),
+            Boundary::Periodic => write!(f, "periodic"),
+            Boundary::Fixed(value) => write!(f, "fixed:{}", value),
+            Boundary::Reflective => write!(f, "reflective"),
+        }
+    }
+}
+// Parse "infinite", "periodic", "reflective", "fixed" (value 0) or "fixed:<value>"
+impl FromStr for Boundary {
+    type Err = String;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "infinite" => Ok(Boundary::Infinite),
+            "periodic" => Ok(Boundary::Periodic),
+            "reflective" => Ok(Boundary::Reflective),
+            "fixed" => Ok(Boundary::Fixed(0)),
+            _ => s
+                .strip_prefix("fixed:")
+                .and_then(|value| value.parse::<u8>().ok())
+                .map(Boundary::Fixed)
+                .ok_or_else(|| format!("Unknown boundary '{}'", s)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn boundary_get_cell() {
+        let cells = [1, 2, 3];
+        let around = |boundary: Boundary| {
+            (-4..7)
+                .map(|idx| boundary.get_cell(&cells, idx))
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(
+            around(Boundary::Infinite),
+            vec![1, 1, 1, 1, 1, 2, 3, 3, 3, 3, 3]
+        );
+        assert_eq!(
+            around(Boundary::Periodic),
+            vec![3, 1, 2, 3, 1, 2, 3, 1, 2, 3, 1]
+        );
+        assert_eq!(
+            around(Boundary::Fixed(0)),
+            vec![0, 0, 0, 0, 1, 2, 3, 0, 0, 0, 0]
+        );
+        assert_eq!(
+            around(Boundary::Reflective),
+            vec![3, 3, 2, 1, 1, 2, 3, 3, 2, 1, 1]
+        );
+        for boundary in [Boundary::Infinite, Boundary::Periodic, Boundary::Reflective].iter() {
+            assert!((-4..7).all(
+                |idx| cells[boundary.index(3, idx).unwrap()] == boundary.get_cell(&cells, idx)
+            ));
+        }
+        assert_eq!(Boundary::Fixed(0).index(3, -1), None);
+        assert_eq!(Boundary::Fixed(0).index(3, 2), Some(2));
+    }
+    #[test]
+    fn boundary_parse() {
+        for boundary in [
+            Boundary::Infinite,
+            Boundary::Periodic,
+            Boundary::Fixed(2),
+            Boundary::Reflective,
+        ]
+        .iter()
+        {
+            assert_eq!(boundary.to_string().parse::<Boundary>(), Ok(*boundary));
+        }
+        assert_eq!("fixed".parse::<Boundary>(), Ok(Boundary::Fixed(0)));
+        assert!("fixed:x".parse::<Boundary>().is_err());
+    }
+}
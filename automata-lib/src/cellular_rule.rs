@@ -0,0 +1,164 @@
+const BLACK: (u8, u8, u8) = (0u8, 0u8, 0u8);
+const BLUE: (u8, u8, u8) = (0u8, 0u8, 192u8);
+const CYAN: (u8, u8, u8) = (0u8, 192u8, 192u8);
+const WHITE: (u8, u8, u8) = (255u8, 255u8, 255u8);
+
+// Largest number of windows checked to detect a linear rule
+const MAX_LINEAR_WINDOWS: usize = 1 << 20;
+
+// Operations needed by an automata to evolve and display its cells.
+// Only the number of colors, the radius and the transition itself must be provided,
+// initialization and display have defaults based on the number of colors.
+pub trait CellularRule {
+    fn get_n_colors(&self) -> u8;
+    // Number of cells on each side of the updated cell that are part of its neighborhood
+    fn get_radius(&self) -> u8;
+    // Return the new value of the center cell of 'cells', a window of 2*radius+1 cells
+    fn apply(&self, cells: &[u8]) -> u8;
+    // Whether the new cells are drawn at random with apply_random.
+    // 'apply' then returns the most likely new cell, for the analyses that need a deterministic rule.
+    fn is_stochastic(&self) -> bool {
+        false
+    }
+    // New value of the center cell of 'cells', drawn with 'random', a uniform number in [0,1[
+    fn apply_random(&self, cells: &[u8], _random: f64) -> u8 {
+        self.apply(cells)
+    }
+    // Initial cells: a single cell of the last color, padded on each side with enough background cells
+    // for the window of the edge cells to only see background.
+    fn initialize(&self) -> Vec<u8> {
+        let padding = 2 * self.get_radius() as usize + 1;
+        let mut cells = vec![0; 2 * padding + 1];
+        cells[padding] = self.get_n_colors() - 1;
+        cells
+    }
+    // Coefficients a_i of a linear rule, whose new cell is sum(a_i * cells[i]) modulo the number of colors.
+    // None when the rule is not linear, or has too many windows to be checked.
+    fn linear_coefficients(&self) -> Option<Vec<u8>> {
+        if self.is_stochastic() {
+            return None;
+        }
+        let n_colors = self.get_n_colors();
+        let window = 2 * self.get_radius() as usize + 1;
+        match (n_colors as usize).checked_pow(window as u32) {
+            Some(n) if n <= MAX_LINEAR_WINDOWS => (),
+            _ => return None,
+        }
+        let coefs = (0..window)
+            .map(|i| {
+                let mut cells = vec![0; window];
+                cells[i] = 1;
+                self.apply(&cells)
+            })
+            .collect::<Vec<_>>();
+        // Check every window, enumerated as the digits of a counter
+        let mut cells = vec![0u8; window];
+        loop {
+            let sum = cells
+                .iter()
+                .zip(&coefs)
+                .map(|(&c, &a)| c as u32 * a as u32)
+                .sum::<u32>();
+            if self.apply(&cells) as u32 != sum % n_colors as u32 {
+                return None;
+            }
+            match cells.iter().position(|&c| c < n_colors - 1) {
+                Some(i) => {
+                    cells[i] += 1;
+                    cells[..i].iter_mut().for_each(|c| *c = 0);
+                }
+                None => return Some(coefs),
+            }
+        }
+    }
+    fn cell_to_text(&self, cell: &u8) -> String {
+        let n_colors = self.get_n_colors();
+        match *cell {
+            0 => String::from(" "),
+            x if x == n_colors - 1 => String::from("*"),
+            x if n_colors <= 4 && x == n_colors - 2 => String::from("+"),
+            _ if n_colors <= 4 => String::from("-"),
+            x => std::char::from_digit(x as u32, 16).unwrap().to_string(),
+        }
+    }
+    // Up to 4 colors, intermediate colors are blue and cyan,
+    // above, intermediate colors are spread on the hue circle.
+    fn cell_to_rgb(&self, cell: &u8) -> (u8, u8, u8) {
+        let n_colors = self.get_n_colors();
+        match *cell {
+            0 => WHITE,
+            x if x == n_colors - 1 => BLACK,
+            x if n_colors <= 4 && x == n_colors - 2 => BLUE,
+            _ if n_colors <= 4 => CYAN,
+            x => hue_to_rgb((x - 1) as f64 / (n_colors - 2) as f64),
+        }
+    }
+}
+// Saturated color at 'hue' in [0,1[ on the color circle, with a value of 192 like blue and cyan
+fn hue_to_rgb(hue: f64) -> (u8, u8, u8) {
+    let h = hue * 6.0;
+    let x = (192.0 * (1.0 - (h % 2.0 - 1.0).abs())).round() as u8;
+    match h as u8 {
+        0 => (192, x, 0),
+        1 => (x, 192, 0),
+        2 => (0, 192, x),
+        3 => (0, x, 192),
+        4 => (x, 0, 192),
+        _ => (192, 0, x),
+    }
+}
+
+// Rule defined by a closure computing the new center cell from its window.
+pub struct FnRule<F: Fn(&[u8]) -> u8> {
+    n_colors: u8,
+    radius: u8,
+    f: F,
+}
+impl<F: Fn(&[u8]) -> u8> FnRule<F> {
+    pub fn new(n_colors: u8, radius: u8, f: F) -> FnRule<F> {
+        FnRule {
+            n_colors,
+            radius,
+            f,
+        }
+    }
+}
+impl<F: Fn(&[u8]) -> u8> CellularRule for FnRule<F> {
+    fn get_n_colors(&self) -> u8 {
+        self.n_colors
+    }
+    fn get_radius(&self) -> u8 {
+        self.radius
+    }
+    #[inline]
+    fn apply(&self, cells: &[u8]) -> u8 {
+        (self.f)(cells)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn fn_rule_works() {
+        // Sum modulo 3 of the 2 neighbors
+        let rule = FnRule::new(3, 1, |cells: &[u8]| (cells[0] + cells[2]) % 3);
+        assert_eq!(rule.apply(&[1, 0, 1]), 2);
+        assert_eq!(rule.apply(&[2, 2, 2]), 1);
+        assert_eq!(rule.initialize(), vec![0, 0, 0, 2, 0, 0, 0]);
+        assert_eq!(rule.cell_to_text(&1), "+");
+    }
+    #[test]
+    fn linear_coefficients_works() {
+        let rule = FnRule::new(3, 1, |cells: &[u8]| (cells[0] + 2 * cells[2]) % 3);
+        assert_eq!(rule.linear_coefficients(), Some(vec![1, 0, 2]));
+        let rule = FnRule::new(3, 1, |cells: &[u8]| (cells[0] + cells[2] + 1) % 3);
+        assert_eq!(rule.linear_coefficients(), None);
+        let rule = FnRule::new(2, 1, |cells: &[u8]| cells[0] * cells[2]);
+        assert_eq!(rule.linear_coefficients(), None);
+        // Window of 2^21 cells, not checked
+        let rule = FnRule::new(2, 10, |_cells: &[u8]| 0);
+        assert_eq!(rule.linear_coefficients(), None);
+    }
+}
@@ -0,0 +1,288 @@
+use crate::analysis::RunStats;
+use crate::automata::Automata1D;
+use crate::boundary::Boundary;
+use crate::cellular_rule::CellularRule;
+use crate::cycle::Cycle;
+use crate::error::{Error, Result};
+use crate::init::InitialCondition;
+use rand::{Rng, SeedableRng};
+use rand_pcg::Pcg32;
+use std::collections::HashMap;
+use std::fmt;
+
+// Number of random lattices where a cell is changed, to measure the spread of changes
+const DAMAGE_RUNS: u64 = 4;
+// Variations of the densities and entropies above 1/STEADY_SCALE are not steady at all
+const STEADY_SCALE: f64 = 20.0;
+
+// Wolfram classes of the behavior of a rule from random cells:
+// Uniform (1) : all the cells end with the same color
+// Periodic (2) : cells end in stable or periodic structures
+// Chaotic (3) : cells stay random, changes spread in all the lattice
+// Complex (4) : localized structures that move and interact over a regular background
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum WolframClass {
+    Uniform = 1,
+    Periodic = 2,
+    Chaotic = 3,
+    Complex = 4,
+}
+impl fmt::Display for WolframClass {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", *self as u8)
+    }
+}
+// Measures of a run that the classification is based on.
+// compression : size of the LZ78 compression of the second half of the run, over the size of the compression
+//            of as many random cells, near 0 for repetitive cells, near 1 for random cells
+// density_variation, entropy_variation : standard deviations of the density of color 0 and of the cell entropy
+//            of each step, on the second half of the run
+// cycle : cycle reached within the run
+// uniform : whether all the cells have the same color on the last step
+// lyapunov : mean speed of the fronts of a change of one cell, in cells per step and per radius
+#[derive(Debug, Clone, PartialEq)]
+pub struct ClassSignals {
+    pub compression: f64,
+    pub density_variation: f64,
+    pub entropy_variation: f64,
+    pub cycle: Option<Cycle>,
+    pub uniform: bool,
+    pub lyapunov: f64,
+}
+#[derive(Debug, Clone, PartialEq)]
+pub struct Classification {
+    pub class: WolframClass,
+    // Score of the class over the sum of the scores of all classes
+    pub confidence: f64,
+    pub signals: ClassSignals,
+}
+// Heuristic classification, from a run of 'steps' steps on a periodic lattice of 'size' random cells.
+// Random cells use 'seed', and a density (k-1)/k so that all colors are equally likely.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Classifier {
+    pub size: u32,
+    pub steps: u32,
+    pub seed: u64,
+}
+impl Default for Classifier {
+    fn default() -> Classifier {
+        Classifier {
+            size: 131,
+            steps: 256,
+            seed: 0,
+        }
+    }
+}
+impl Classifier {
+    pub fn classify<R: CellularRule + Clone>(&self, rule: &R) -> Result<Classification> {
+        let signals = self.signals(rule)?;
+        let scores = scores(&signals, self.steps);
+        let (class, score) =
+            scores
+                .iter()
+                .copied()
+                .fold((WolframClass::Uniform, 0.0), |best, (class, score)| {
+                    if score > best.1 {
+                        (class, score)
+                    } else {
+                        best
+                    }
+                });
+        let total = scores.iter().map(|&(_c, score)| score).sum::<f64>();
+        Ok(Classification {
+            class,
+            confidence: if total > 0.0 { score / total } else { 0.0 },
+            signals,
+        })
+    }
+    // Signals of the run, it needs at least one cell and 2 steps, a measured half and a transient half
+    pub fn signals<R: CellularRule + Clone>(&self, rule: &R) -> Result<ClassSignals> {
+        if self.size == 0 {
+            return Err(Error::InvalidSize(self.size));
+        }
+        if self.steps < 2 {
+            return Err(Error::InvalidSteps {
+                steps: self.steps,
+                min: 2,
+            });
+        }
+        let n_colors = rule.get_n_colors();
+        let automata_from = |seed| {
+            let init = InitialCondition::Random {
+                density: (n_colors - 1) as f64 / n_colors as f64,
+                seed,
+            };
+            Automata1D::with_init(
+                rule.clone(),
+                Boundary::Periodic,
+                self.size,
+                &init,
+                0,
+                self.size,
+            )
+        };
+        let mut automata = automata_from(self.seed)?;
+        let cycle = automata.find_cycle(self.steps)?;
+        // Changes travel at most 'radius' cells per step on each side, the center cell is changed
+        // and followed until the fronts could meet around the lattice.
+        // A single change can vanish by chance, so the speeds are averaged over a few random cells.
+        let radius = rule.get_radius() as u32;
+        let damage_steps = (self.size / (4 * radius)).max(2);
+        let mut lyapunov = 0.0;
+        for i in 0..DAMAGE_RUNS {
+            let damage = automata_from(self.seed.wrapping_add(i))?
+                .damage(self.size as i32 / 2, damage_steps)?;
+            lyapunov += (damage.left_lyapunov + damage.right_lyapunov) / 2.0;
+        }
+        let lyapunov = lyapunov / DAMAGE_RUNS as f64 / radius as f64;
+        // Only the second half of the run is measured, after most transients
+        automata.step(self.steps / 2);
+        let diagram = automata.space_time(self.steps - self.steps / 2);
+        let stats = RunStats::new(&diagram, n_colors, 0, Vec::new());
+        let uniform = stats.densities[stats.densities.len() - 1].contains(&1.0);
+        Ok(ClassSignals {
+            compression: compression_ratio(diagram.as_slice(), n_colors)
+                / random_compression_ratio(diagram.as_slice().len(), n_colors),
+            density_variation: variation(stats.densities.iter().map(|d| d[0])),
+            entropy_variation: variation(stats.densities.iter().map(|d| {
+                d.iter()
+                    .filter(|&&p| p > 0.0)
+                    .fold(0.0, |entropy, &p| entropy - p * p.log2())
+            })),
+            cycle,
+            uniform,
+            lyapunov,
+        })
+    }
+}
+// Score of each class, from the signals and the number of steps of the run.
+// A cycle reached within the first half of the run settles the rule in class 1 or 2, the earlier the surer.
+// Otherwise, with 'r' the randomness of the cells and 's' the spread of changes, both from 0 to 1:
+// periodic rules do not spread and are either not random or steady, with densities and entropies
+// that do not vary, chaotic rules are random and spreading,
+// and complex rules are in between for both, their score peaks at r=s=0.5.
+fn scores(signals: &ClassSignals, steps: u32) -> [(WolframClass, f64); 4] {
+    let horizon = (steps / 2).max(1) as f64;
+    let settled = match signals.cycle {
+        Some(cycle) => (1.0 - (cycle.transient + cycle.period) as f64 / horizon).max(0.0),
+        None => 0.0,
+    };
+    let r = signals.compression.clamp(0.0, 1.0);
+    let s = signals.lyapunov.clamp(0.0, 1.0);
+    let steady =
+        1.0 - (STEADY_SCALE * (signals.density_variation + signals.entropy_variation)).min(1.0);
+    let (uniform, periodic) = if signals.uniform {
+        (settled, 0.0)
+    } else {
+        (0.0, settled)
+    };
+    let unsettled = 1.0 - settled;
+    [
+        (WolframClass::Uniform, uniform),
+        (
+            WolframClass::Periodic,
+            periodic + unsettled * (1.0 - r).max(steady) * (1.0 - s),
+        ),
+        (WolframClass::Chaotic, unsettled * r * s),
+        (
+            WolframClass::Complex,
+            unsettled * 16.0 * r * (1.0 - r) * s * (1.0 - s),
+        ),
+    ]
+}
+// Standard deviation of the values
+fn variation<I: Iterator<Item = f64> + Clone>(values: I) -> f64 {
+    let n = values.clone().count().max(1) as f64;
+    let mean = values.clone().sum::<f64>() / n;
+    (values.map(|v| (v - mean) * (v - mean)).sum::<f64>() / n).sqrt()
+}
+// Compression ratio of 'len' random cells, with a fixed seed so that classifications are reproducible
+fn random_compression_ratio(len: usize, n_colors: u8) -> f64 {
+    let mut rng = Pcg32::seed_from_u64(0);
+    let cells = (0..len)
+        .map(|_i| rng.gen_range(0, n_colors))
+        .collect::<Vec<_>>();
+    compression_ratio(&cells, n_colors)
+}
+// Size of the LZ78 compression of the cells over their size, for cells of 'n_colors' colors.
+// Cells are split in phrases that are a previous phrase followed by one cell,
+// each phrase is stored as the index of the previous phrase and the cell.
+pub(crate) fn compression_ratio(cells: &[u8], n_colors: u8) -> f64 {
+    if cells.is_empty() {
+        return 0.0;
+    }
+    let mut phrases = HashMap::new();
+    let mut current = 0usize;
+    let mut n_phrases = 0usize;
+    for &c in cells {
+        match phrases.get(&(current, c)) {
+            Some(&next) => current = next,
+            None => {
+                n_phrases += 1;
+                phrases.insert((current, c), n_phrases);
+                current = 0;
+            }
+        }
+    }
+    if current != 0 {
+        n_phrases += 1;
+    }
+    let cell_bits = (n_colors as f64).log2();
+    n_phrases as f64 * (n_phrases as f64).log2() / (cells.len() as f64 * cell_bits)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rules::Rule1D;
+
+    #[test]
+    fn classify_works() {
+        let classifier = Classifier::default();
+        let class = |nb| classifier.classify(&Rule1D::new(2, nb)).unwrap();
+        for &(nbs, expected) in &[
+            (&[0u64, 8, 32][..], WolframClass::Uniform),
+            (&[4, 50, 108, 170, 204][..], WolframClass::Periodic),
+            (&[30, 45, 90, 106, 150][..], WolframClass::Chaotic),
+            // Rule 110 and its equivalent rules
+            (&[54, 110, 124, 137, 193][..], WolframClass::Complex),
+        ] {
+            for &nb in nbs {
+                let classification = class(nb);
+                assert_eq!(classification.class, expected, "rule {}", nb);
+                assert!(classification.confidence > 0.5, "rule {}", nb);
+            }
+        }
+        let signals = class(30).signals;
+        assert!(signals.compression > 0.95 && signals.cycle.is_none() && !signals.uniform);
+        assert_eq!(class(0).signals.cycle.unwrap().period, 1);
+        assert!(class(0).signals.uniform);
+        // Same classification from the same seed
+        assert_eq!(class(110), class(110));
+        // Runs need cells and steps to measure
+        let rule = Rule1D::new(2, 110);
+        let classify = |size, steps| {
+            Classifier {
+                size,
+                steps,
+                seed: 0,
+            }
+            .classify(&rule)
+        };
+        assert!(matches!(classify(0, 256), Err(Error::InvalidSize(0))));
+        assert!(matches!(
+            classify(131, 0),
+            Err(Error::InvalidSteps { steps: 0, min: 2 })
+        ));
+        assert!(classify(131, 1).is_err());
+        assert!(classify(1, 2).is_ok());
+        assert!(classify(131, 2).is_ok());
+    }
+    #[test]
+    fn compression_ratio_works() {
+        assert!(compression_ratio(&[0; 10000], 2) < 0.2);
+        let ratio = random_compression_ratio(10000, 3);
+        assert!(ratio > 0.9 && ratio < 1.5);
+        assert_eq!(compression_ratio(&[], 2), 0.0);
+    }
+}
@@ -0,0 +1,71 @@
+// Attractor reached by an automata on a finite lattice.
+// After 'transient' steps, the cells repeat every 'period' steps, moved by 'shift' cells:
+// cell x at step t+period is cell x-shift at step t, positive shifts travel to the right.
+// Only periodic lattices have traveling cycles, the shift is always 0 on other boundaries.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Cycle {
+    pub transient: u32,
+    pub period: u32,
+    pub shift: i32,
+}
+
+// Start of the lexicographically smallest rotation of the ring 'cells'
+pub(crate) fn least_rotation(cells: &[u8]) -> usize {
+    let n = cells.len();
+    let (mut i, mut j, mut k) = (0, 1, 0);
+    while i < n && j < n && k < n {
+        let (a, b) = (cells[(i + k) % n], cells[(j + k) % n]);
+        if a == b {
+            k += 1;
+            continue;
+        }
+        if a > b {
+            i += k + 1;
+        } else {
+            j += k + 1;
+        }
+        if i == j {
+            j += 1;
+        }
+        k = 0;
+    }
+    i.min(j)
+}
+
+// Smallest rotation that leaves the ring 'cells' unchanged, a divisor of its size
+pub(crate) fn rotation_period(cells: &[u8]) -> usize {
+    let n = cells.len();
+    // Prefix function: longest proper prefix of cells[..=i] that is also its suffix
+    let mut prefix = vec![0usize; n];
+    for i in 1..n {
+        let mut k = prefix[i - 1];
+        while k > 0 && cells[i] != cells[k] {
+            k = prefix[k - 1];
+        }
+        if cells[i] == cells[k] {
+            k += 1;
+        }
+        prefix[i] = k;
+    }
+    let period = n - prefix[n - 1];
+    if n.is_multiple_of(period) {
+        period
+    } else {
+        n
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rotation_works() {
+        assert_eq!(least_rotation(&[1, 0, 1, 1, 0, 0]), 4);
+        assert_eq!(least_rotation(&[2, 2, 2]), 0);
+        assert_eq!(least_rotation(&[1]), 0);
+        assert_eq!(rotation_period(&[1, 0, 1, 0, 1, 0]), 2);
+        assert_eq!(rotation_period(&[1, 0, 1, 1, 0]), 5);
+        assert_eq!(rotation_period(&[0, 0, 0]), 1);
+    }
+}
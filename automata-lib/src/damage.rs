@@ -0,0 +1,147 @@
+use crate::analysis::slope;
+use crate::automata::Automata1D;
+use crate::cellular_rule::CellularRule;
+use crate::error::Result;
+use crate::spacetime::SpaceTime;
+use image::RgbImage;
+
+// Color of the cells that differ between the two copies, in the overlay image
+const DAMAGE_RGB: (u8, u8, u8) = (255, 0, 0);
+
+// Damage spreading: an automata and a copy that differs in one cell, evolved side by side.
+// cells : the view of the original automata on each step
+// damage : 1 where the copies differ on the view, 0 elsewhere
+// hamming : number of cells that differ on each step, on the whole lattice
+// fronts : positions of the leftmost and rightmost differing cells on each step, None once the copies agree
+// left_lyapunov, right_lyapunov : speeds of the left front to the left and of the right front to the right,
+//            in cells per step, the slopes of their least squares lines while the copies differ
+#[derive(Debug, Clone, PartialEq)]
+pub struct Damage {
+    pub cells: SpaceTime,
+    pub damage: SpaceTime,
+    pub hamming: Vec<u64>,
+    pub fronts: Vec<Option<(i64, i64)>>,
+    pub left_lyapunov: f64,
+    pub right_lyapunov: f64,
+}
+impl Damage {
+    // Original cells with the differing cells in red
+    pub fn to_image<R: CellularRule>(&self, rule: &R) -> RgbImage {
+        let buf = self
+            .cells
+            .as_slice()
+            .iter()
+            .zip(self.damage.as_slice())
+            .flat_map(|(c, &d)| {
+                let (r, g, b) = if d == 0 {
+                    rule.cell_to_rgb(c)
+                } else {
+                    DAMAGE_RGB
+                };
+                vec![r, g, b]
+            })
+            .collect::<Vec<_>>();
+        RgbImage::from_raw(self.cells.width(), self.cells.height(), buf).unwrap()
+    }
+    // Original cells with the differing cells as 'x', framed like the automata text view
+    pub fn to_text<R: CellularRule>(&self, rule: &R) -> String {
+        self.cells
+            .rows()
+            .zip(self.damage.rows())
+            .map(|(row, damage)| {
+                let line = row
+                    .iter()
+                    .zip(damage)
+                    .map(|(c, &d)| {
+                        if d == 0 {
+                            rule.cell_to_text(c)
+                        } else {
+                            String::from("x")
+                        }
+                    })
+                    .collect::<Vec<_>>()
+                    .join("");
+                format!("|{}|\n", line)
+            })
+            .collect()
+    }
+}
+impl<R: CellularRule + Clone> Automata1D<R> {
+    // Change the cell at position 'x' to the next color in a copy of the automata,
+    // then step both 'n_step' times.
+    pub fn damage(&mut self, x: i32, n_step: u32) -> Result<Damage> {
+        let mut copy = self.clone();
+        let n_colors = self.get_rule().get_n_colors();
+        copy.set_cell(x, (self.get_cell(x) + 1) % n_colors)?;
+        // Empty diagram of the view, from the current step
+        let cells = self.space_time(0);
+        let (x_start, t_start, width) = (cells.x_start(), cells.t_start(), cells.width());
+        let mut damage = Damage {
+            cells,
+            damage: SpaceTime::new(x_start, t_start, width),
+            hamming: Vec::with_capacity(n_step as usize),
+            fronts: Vec::with_capacity(n_step as usize),
+            left_lyapunov: 0.0,
+            right_lyapunov: 0.0,
+        };
+        for _i in 0..n_step {
+            damage.cells.push_row(self.iter());
+            damage
+                .damage
+                .push_row(self.iter().zip(copy.iter()).map(|(a, b)| (a != b) as u8));
+            // Beyond the cells of both copies, the boundary gives the same cells
+            let (start, end) = (self.cell_range(), copy.cell_range());
+            let differ = (start.0.min(end.0)..start.1.max(end.1))
+                .filter(|&x| self.get_cell(x as i32) != copy.get_cell(x as i32))
+                .collect::<Vec<_>>();
+            damage.hamming.push(differ.len() as u64);
+            damage.fronts.push(
+                differ
+                    .first()
+                    .map(|&first| (first, *differ.last().unwrap())),
+            );
+            self.step(1);
+            copy.step(1);
+        }
+        let fronts = damage.fronts.iter().map_while(|&f| f).collect::<Vec<_>>();
+        damage.left_lyapunov = slope(&fronts.iter().map(|f| -f.0 as f64).collect::<Vec<_>>());
+        damage.right_lyapunov = slope(&fronts.iter().map(|f| f.1 as f64).collect::<Vec<_>>());
+        Ok(damage)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::boundary::Boundary;
+    use crate::rules::Rule1D;
+    use crate::Automata1D;
+
+    #[test]
+    fn damage_works() {
+        // Rule 90 is additive, the damage is a Sierpinski triangle growing one cell per step on each side
+        let mut automata = Automata1D::new(Rule1D::new(2, 90), -8, 17);
+        let damage = automata.damage(3, 8).unwrap();
+        assert_eq!(automata.get_cur_step(), 8);
+        assert_eq!(damage.hamming[..4], [1, 2, 2, 4]);
+        assert_eq!(damage.fronts[..2], [Some((3, 3)), Some((2, 4))]);
+        assert_eq!(damage.left_lyapunov, 1.0);
+        assert_eq!(damage.right_lyapunov, 1.0);
+        assert_eq!(damage.damage.row(1).unwrap()[10..13], [1, 0, 1]);
+        assert_eq!(damage.cells.row(0).unwrap()[8], 1);
+        // Rule 204 is the identity, the damage stays in place
+        let mut automata = Automata1D::new(Rule1D::new(2, 204), -4, 9);
+        let damage = automata.damage(-10, 5).unwrap();
+        assert_eq!(damage.hamming, vec![1; 5]);
+        assert_eq!((damage.left_lyapunov, damage.right_lyapunov), (0.0, 0.0));
+        assert_eq!(damage.to_text(automata.get_rule()).lines().count(), 5);
+        // Rule 0 heals the damage after one step
+        let mut automata =
+            Automata1D::with_boundary(Rule1D::new(2, 0), Boundary::Periodic, 10, 0, 10).unwrap();
+        let damage = automata.damage(2, 3).unwrap();
+        assert_eq!(damage.hamming, vec![1, 0, 0]);
+        assert_eq!(damage.fronts, vec![Some((2, 2)), None, None]);
+        let image = damage.to_image(automata.get_rule());
+        assert_eq!(image.get_pixel(2, 0).0, [255, 0, 0]);
+        assert!(automata.damage(10, 1).is_err());
+    }
+}
@@ -0,0 +1,254 @@
+use crate::cellular_rule::CellularRule;
+use crate::error::{Error, Result};
+use std::collections::{HashMap, VecDeque};
+
+// Largest number of edges of the pair graph, the product of the de Bruijn graph with itself
+const MAX_PAIR_EDGES: u64 = 1 << 22;
+// Largest number of sets of nodes explored in the search of the shortest Garden-of-Eden pattern
+const MAX_SUBSETS: usize = 1 << 20;
+
+// De Bruijn graph of a rule of radius r: nodes are the words of 2r cells, each window of 2r+1 cells
+// is an edge from the node of its first 2r cells to the node of its last 2r cells, labelled by the new
+// center cell. Configurations are the paths of the graph and their images the labels of the paths.
+// Words and windows are numbers whose digits are the cells, the first cell is the most significant.
+#[derive(Debug, Clone, PartialEq)]
+pub struct DeBruijn {
+    n_colors: u8,
+    radius: u8,
+    // New center cell of each window
+    labels: Vec<u8>,
+}
+impl DeBruijn {
+    pub fn new<R: CellularRule>(rule: &R) -> Result<DeBruijn> {
+        let (n_colors, radius) = (rule.get_n_colors(), rule.get_radius());
+        let too_large = || Error::GraphTooLarge { n_colors, radius };
+        let n_pair_edges = (n_colors as u64)
+            .checked_pow(4 * radius as u32 + 2)
+            .ok_or_else(too_large)?;
+        if n_pair_edges > MAX_PAIR_EDGES {
+            return Err(too_large());
+        }
+        let window = 2 * radius as usize + 1;
+        let n_windows = (n_colors as usize).pow(window as u32);
+        let mut cells = vec![0u8; window];
+        let labels = (0..n_windows)
+            .map(|w| {
+                let mut digits = w;
+                for c in cells.iter_mut().rev() {
+                    *c = (digits % n_colors as usize) as u8;
+                    digits /= n_colors as usize;
+                }
+                rule.apply(&cells)
+            })
+            .collect();
+        Ok(DeBruijn {
+            n_colors,
+            radius,
+            labels,
+        })
+    }
+    pub fn n_nodes(&self) -> usize {
+        self.labels.len() / self.n_colors as usize
+    }
+    pub fn n_edges(&self) -> usize {
+        self.labels.len()
+    }
+    // Label of the edge of window 'w'
+    pub fn label(&self, w: usize) -> u8 {
+        self.labels[w]
+    }
+    // Edges from 'node', as (window, target node)
+    fn edges(&self, node: usize) -> impl Iterator<Item = (usize, usize)> + '_ {
+        let k = self.n_colors as usize;
+        let n_nodes = self.n_nodes();
+        (node * k..(node + 1) * k).map(move |w| (w, w % n_nodes))
+    }
+    // Pairs of edges with the same label from the pair of nodes (u,v), as target pairs
+    fn pair_successors(&self, u: usize, v: usize) -> impl Iterator<Item = (usize, usize)> + '_ {
+        self.edges(u).flat_map(move |(wu, tu)| {
+            self.edges(v)
+                .filter(move |&(wv, _tv)| self.labels[wu] == self.labels[wv])
+                .map(move |(_wv, tv)| (tu, tv))
+        })
+    }
+    // The global map is surjective when every pattern has a preimage, which by the Garden-of-Eden theorem
+    // holds when no two distinct paths with the same label start and end at the same nodes.
+    // In the pair graph, that is no path from the diagonal to the diagonal through a pair of distinct nodes.
+    pub fn is_surjective(&self) -> bool {
+        let n_nodes = self.n_nodes();
+        let mut seen = vec![false; n_nodes * n_nodes];
+        let mut queue = VecDeque::new();
+        for u in 0..n_nodes {
+            for (a, b) in self.pair_successors(u, u).filter(|&(a, b)| a != b) {
+                if !seen[a * n_nodes + b] {
+                    seen[a * n_nodes + b] = true;
+                    queue.push_back((a, b));
+                }
+            }
+        }
+        while let Some((u, v)) = queue.pop_front() {
+            for (a, b) in self.pair_successors(u, v) {
+                if a == b {
+                    return false;
+                }
+                if !seen[a * n_nodes + b] {
+                    seen[a * n_nodes + b] = true;
+                    queue.push_back((a, b));
+                }
+            }
+        }
+        true
+    }
+    // The global map is injective, the rule is reversible, when two bi-infinite paths with the same label
+    // are equal, that is when only pairs of equal nodes are on bi-infinite paths of the pair graph.
+    // Pairs without successor or without predecessor are removed until all remaining pairs have both,
+    // the remaining pairs are the ones on bi-infinite paths.
+    pub fn is_injective(&self) -> bool {
+        let n_nodes = self.n_nodes();
+        let n_pairs = n_nodes * n_nodes;
+        let mut successors = vec![Vec::new(); n_pairs];
+        let mut predecessors = vec![Vec::new(); n_pairs];
+        for u in 0..n_nodes {
+            for v in 0..n_nodes {
+                for (a, b) in self.pair_successors(u, v) {
+                    successors[u * n_nodes + v].push(a * n_nodes + b);
+                    predecessors[a * n_nodes + b].push(u * n_nodes + v);
+                }
+            }
+        }
+        let mut out_degrees = successors.iter().map(Vec::len).collect::<Vec<_>>();
+        let mut in_degrees = predecessors.iter().map(Vec::len).collect::<Vec<_>>();
+        let mut removed = vec![false; n_pairs];
+        let mut queue = (0..n_pairs)
+            .filter(|&p| out_degrees[p] == 0 || in_degrees[p] == 0)
+            .collect::<VecDeque<_>>();
+        while let Some(p) = queue.pop_front() {
+            if removed[p] {
+                continue;
+            }
+            removed[p] = true;
+            for &s in successors[p].iter() {
+                in_degrees[s] -= 1;
+                if in_degrees[s] == 0 {
+                    queue.push_back(s);
+                }
+            }
+            for &s in predecessors[p].iter() {
+                out_degrees[s] -= 1;
+                if out_degrees[s] == 0 {
+                    queue.push_back(s);
+                }
+            }
+        }
+        (0..n_pairs).all(|p| removed[p] || p / n_nodes == p % n_nodes)
+    }
+    // Shortest Garden-of-Eden pattern, a pattern of cells that no configuration has as image,
+    // None when the global map is surjective.
+    // The sets of nodes where the paths labelled by each pattern can end are explored in breadth first order,
+    // from the set of all nodes, until the empty set.
+    pub fn garden_of_eden(&self) -> Result<Option<Vec<u8>>> {
+        if self.is_surjective() {
+            return Ok(None);
+        }
+        let n_nodes = self.n_nodes();
+        let all = (0..n_nodes).collect::<Vec<_>>();
+        // Pattern to each explored set, as the set it extends and its last cell
+        let mut parents: HashMap<Vec<usize>, Option<(Vec<usize>, u8)>> = HashMap::new();
+        parents.insert(all.clone(), None);
+        let mut queue = VecDeque::from(vec![all]);
+        while let Some(nodes) = queue.pop_front() {
+            for c in 0..self.n_colors {
+                let mut next = nodes
+                    .iter()
+                    .flat_map(|&node| self.edges(node))
+                    .filter(|&(w, _t)| self.labels[w] == c)
+                    .map(|(_w, t)| t)
+                    .collect::<Vec<_>>();
+                if next.is_empty() {
+                    let mut pattern = vec![c];
+                    let mut set = &nodes;
+                    while let Some((parent, c)) = &parents[set] {
+                        pattern.push(*c);
+                        set = parent;
+                    }
+                    pattern.reverse();
+                    return Ok(Some(pattern));
+                }
+                next.sort_unstable();
+                next.dedup();
+                if !parents.contains_key(&next) {
+                    if parents.len() >= MAX_SUBSETS {
+                        return Err(Error::GraphTooLarge {
+                            n_colors: self.n_colors,
+                            radius: self.radius,
+                        });
+                    }
+                    parents.insert(next.clone(), Some((nodes.clone(), c)));
+                    queue.push_back(next);
+                }
+            }
+        }
+        // Not reached, a non surjective map has a Garden-of-Eden pattern
+        Ok(None)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cellular_rule::FnRule;
+    use crate::rules::Rule1D;
+
+    // Whether some cells have 'pattern' as image
+    fn has_preimage(rule: &Rule1D, pattern: &[u8]) -> bool {
+        let window = 2 * rule.get_radius() as usize + 1;
+        let len = pattern.len() + window - 1;
+        let k = rule.get_n_colors() as usize;
+        (0..k.pow(len as u32)).any(|n| {
+            let cells = (0..len)
+                .map(|i| (n / k.pow(i as u32) % k) as u8)
+                .collect::<Vec<_>>();
+            cells
+                .windows(window)
+                .zip(pattern)
+                .all(|(w, &p)| rule.apply(w) == p)
+        })
+    }
+    #[test]
+    fn de_bruijn_works() {
+        let graph = DeBruijn::new(&Rule1D::new(2, 110)).unwrap();
+        assert_eq!((graph.n_nodes(), graph.n_edges()), (4, 8));
+        // Window 110 has number 6
+        assert_eq!(graph.label(6), 1);
+        assert_eq!(graph.label(7), 0);
+        // 30 elementary rules are surjective, 6 of them are injective: identity, shifts and complements
+        let graphs = (0..256)
+            .map(|nb| DeBruijn::new(&Rule1D::new(2, nb)).unwrap())
+            .collect::<Vec<_>>();
+        let surjective = (0..256).filter(|&nb| graphs[nb].is_surjective());
+        assert_eq!(surjective.clone().count(), 30);
+        assert!(surjective.clone().any(|nb| nb == 30) && surjective.clone().any(|nb| nb == 90));
+        let injective = (0..256)
+            .filter(|&nb| graphs[nb].is_injective())
+            .collect::<Vec<_>>();
+        assert_eq!(injective, vec![15, 51, 85, 170, 204, 240]);
+        assert_eq!(graphs[30].garden_of_eden().unwrap(), None);
+        assert_eq!(graphs[0].garden_of_eden().unwrap(), Some(vec![1]));
+        for nb in (0..256).filter(|&nb| !graphs[nb].is_surjective()) {
+            let pattern = graphs[nb].garden_of_eden().unwrap().unwrap();
+            let rule = Rule1D::new(2, nb as u64);
+            assert!(!has_preimage(&rule, &pattern), "rule {}", nb);
+            // Shortest pattern
+            let shorter = pattern.len() - 1;
+            assert!((0..1 << shorter).all(|n| {
+                let pattern = (0..shorter).map(|i| (n >> i & 1) as u8).collect::<Vec<_>>();
+                has_preimage(&rule, &pattern)
+            }));
+        }
+        // Rule 110 and the sum modulo 3 of the neighbors
+        assert_eq!(graphs[110].garden_of_eden().unwrap().unwrap().len(), 5);
+        let graph = DeBruijn::new(&FnRule::new(3, 1, |c: &[u8]| (c[0] + c[2]) % 3)).unwrap();
+        assert!(graph.is_surjective() && !graph.is_injective());
+        assert!(DeBruijn::new(&FnRule::new(2, 8, |_c: &[u8]| 0)).is_err());
+    }
+}
@@ -0,0 +1,220 @@
+use crate::boundary::Boundary;
+use crate::rules::RuleKind;
+use crate::update::UpdateScheme;
+use num_bigint::BigUint;
+use std::fmt;
+
+#[derive(Debug)]
+pub enum Error {
+    // Number of colors outside of 2..=MAX_COLORS
+    InvalidColors(u8),
+    // Radius of 0
+    InvalidRadius(u8),
+    // Output table of this kind, colors and radius is too large to be stored
+    RuleTooLarge {
+        kind: RuleKind,
+        n_colors: u8,
+        radius: u8,
+    },
+    RuleOutOfRange {
+        rule_nb: BigUint,
+        max: BigUint,
+    },
+    // Finite lattice of size 0
+    InvalidSize(u32),
+    // Run of fewer steps than the operation needs, with the smallest number of steps
+    InvalidSteps {
+        steps: u32,
+        min: u32,
+    },
+    // Fixed boundary value that is not a color of the rule
+    InvalidBoundaryValue(u8),
+    // Boundary that the automata cannot model
+    UnsupportedBoundary(Boundary),
+    // Random initial density outside of [0,1]
+    InvalidDensity(f64),
+    // Initial pattern that cannot be used, with the reason
+    InvalidPattern(String),
+    // Target step before the current step of the automata
+    StepInPast {
+        step: u32,
+        current: u32,
+    },
+    // Checkpoint that cannot be read, with the reason
+    InvalidCheckpoint(String),
+    // Color permutation that is not a permutation of the rule colors, or that the rule kind cannot encode
+    InvalidPermutation(Vec<u8>),
+    // Rules equivalent to a rule of this kind, colors and radius are too many to be enumerated
+    TooManyEquivalents {
+        kind: RuleKind,
+        n_colors: u8,
+        radius: u8,
+    },
+    // Cell position beyond the range of the lattice positions
+    PositionOutOfRange(i64),
+    // Going back 'n_step' steps from step 'current', before the first step
+    StepBeforeStart {
+        n_step: u32,
+        current: u32,
+    },
+    // Going forward 'n_step' steps from step 'current', beyond the last step 'max' the automata can reach
+    StepOutOfRange {
+        n_step: u64,
+        current: u64,
+        max: u64,
+    },
+    // Probability distribution of a stochastic rule that cannot be used, with the reason
+    InvalidDistribution(String),
+    // Stochastic rule given to an automata that only supports deterministic rules
+    StochasticRule,
+    // Update scheme that cannot be used, with the reason
+    InvalidUpdateScheme(String),
+    // Update scheme that the operation cannot model
+    UnsupportedUpdateScheme(UpdateScheme),
+    // De Bruijn graph of a rule of these colors and radius that is too large to be analyzed
+    GraphTooLarge {
+        n_colors: u8,
+        radius: u8,
+    },
+    // Options of a command line that cannot be used together, with the reason
+    IncompatibleOptions(String),
+    Image(image::ImageError),
+    Io(std::io::Error),
+}
+pub type Result<T> = std::result::Result<T, Error>;
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Error::InvalidColors(n_colors) => write!(
+                f,
+                "{} colors is not supported, number of colors must be between 2 and {}",
+                n_colors,
+                crate::rules::MAX_COLORS
+            ),
+            Error::InvalidRadius(radius) => {
+                write!(
+                    f,
+                    "Radius {} is not supported, radius must be at least 1",
+                    radius
+                )
+            }
+            Error::RuleTooLarge {
+                kind,
+                n_colors,
+                radius,
+            } => write!(
+                f,
+                "The {} rule table for {} colors and radius {} is too large",
+                kind, n_colors, radius
+            ),
+            Error::RuleOutOfRange { rule_nb, max } => write!(
+                f,
+                "Rule number {} is out of range, maximum is {}",
+                rule_nb, max
+            ),
+            Error::InvalidSize(size) => write!(
+                f,
+                "Lattice size {} is not supported, a finite lattice needs at least one cell",
+                size
+            ),
+            Error::InvalidSteps { steps, min } => write!(
+                f,
+                "Runs of {} steps are not supported, at least {} steps are needed",
+                steps, min
+            ),
+            Error::InvalidBoundaryValue(value) => write!(
+                f,
+                "Fixed boundary value {} is not a color of the rule",
+                value
+            ),
+            Error::UnsupportedBoundary(boundary) => {
+                write!(f, "Boundary {} is not supported by this automata", boundary)
+            }
+            Error::InvalidDensity(density) => write!(
+                f,
+                "Density {} is not supported, density must be between 0 and 1",
+                density
+            ),
+            Error::InvalidPattern(reason) => write!(f, "Invalid initial pattern: {}", reason),
+            Error::StepInPast { step, current } => write!(
+                f,
+                "Cannot go back to step {}, the automata is at step {}",
+                step, current
+            ),
+            Error::InvalidCheckpoint(reason) => write!(f, "Invalid checkpoint: {}", reason),
+            Error::InvalidPermutation(perm) => write!(
+                f,
+                "Color permutation {:?} cannot be applied to this rule",
+                perm
+            ),
+            Error::TooManyEquivalents {
+                kind,
+                n_colors,
+                radius,
+            } => write!(
+                f,
+                "Equivalent rules of the {} rules for {} colors and radius {} are too many to be enumerated",
+                kind, n_colors, radius
+            ),
+            Error::PositionOutOfRange(x) => write!(
+                f,
+                "Cell position {} is out of range, positions are between {} and {}",
+                x,
+                i32::MIN,
+                i32::MAX
+            ),
+            Error::StepBeforeStart { n_step, current } => write!(
+                f,
+                "Cannot go back {} steps, the automata is at step {}",
+                n_step, current
+            ),
+            Error::StepOutOfRange {
+                n_step,
+                current,
+                max,
+            } => write!(
+                f,
+                "Cannot go forward {} steps from step {}, the automata stops at step {}",
+                n_step, current, max
+            ),
+            Error::InvalidDistribution(reason) => {
+                write!(f, "Invalid probability distribution: {}", reason)
+            }
+            Error::StochasticRule => write!(f, "Stochastic rules are not supported by this automata"),
+            Error::InvalidUpdateScheme(reason) => write!(f, "Invalid update scheme: {}", reason),
+            Error::UnsupportedUpdateScheme(update) => write!(
+                f,
+                "Update scheme {} is not supported by this operation",
+                update
+            ),
+            Error::GraphTooLarge { n_colors, radius } => write!(
+                f,
+                "The de Bruijn graph for {} colors and radius {} is too large to be analyzed",
+                n_colors, radius
+            ),
+            Error::IncompatibleOptions(reason) => write!(f, "Incompatible options: {}", reason),
+            Error::Image(e) => write!(f, "Image error: {}", e),
+            Error::Io(e) => write!(f, "IO error: {}", e),
+        }
+    }
+}
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Image(e) => Some(e),
+            Error::Io(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+impl From<image::ImageError> for Error {
+    fn from(e: image::ImageError) -> Self {
+        Error::Image(e)
+    }
+}
+impl From<std::io::Error> for Error {
+    fn from(e: std::io::Error) -> Self {
+        Error::Io(e)
+    }
+}
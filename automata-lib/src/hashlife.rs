@@ -0,0 +1,515 @@
+use crate::cellular_rule::CellularRule;
+use crate::error::{Error, Result};
+use crate::init::InitialCondition;
+use crate::spacetime::SpaceTime;
+use image::RgbImage;
+use std::collections::HashMap;
+
+// Above this number of nodes, the nodes that are not part of the current cells are dropped
+const MAX_NODES: usize = 1 << 21;
+
+type NodeId = u32;
+
+// Node of 2^level cells, made of two nodes of 2^(level-1) cells.
+// Nodes of level 0 are single cells, their 'left' is their color.
+#[derive(Debug, Clone, Copy)]
+struct Node {
+    level: u8,
+    left: NodeId,
+    right: NodeId,
+}
+
+// Automata on an infinite lattice, that can jump far ahead in time.
+// Cells are stored as a binary tree of nodes where identical nodes are shared, and the result
+// of each node after 2^j steps is memoized. Patterns with regularities in space and time, like
+// most patterns of rule 90 or rule 110, can be advanced by 2^j steps in far less than 2^j operations.
+// Unlike Automata1D, steps are counted in u64 to jump beyond 2^32 steps, up to HashLife1D::MAX_STEP.
+pub struct HashLife1D<R: CellularRule> {
+    rule: R,
+    nodes: Vec<Node>,
+    // Node made of each pair of children
+    joins: HashMap<(NodeId, NodeId), NodeId>,
+    // Center half of a node after 2^j steps, for each node and j
+    results: HashMap<(NodeId, u8), NodeId>,
+    // Node with all its cells of the same color, for each color and level
+    uniforms: Vec<Vec<NodeId>>,
+    // Smallest level whose center half is known after one step, its result is computed cell by cell
+    base_level: u8,
+    root: NodeId,
+    // Position of the first cell of the root
+    origin: i64,
+    // Color of the cells on the left and on the right of the root
+    background: (u8, u8),
+    step: u64,
+    view_start: i32,
+    view_width: u32,
+}
+impl<R: CellularRule> HashLife1D<R> {
+    // Last step the automata can reach. The cells spread by at most 255 cells per step, so positions
+    // and node sizes stay below 2^57 and the shifts on positions and levels cannot overflow an i64.
+    pub const MAX_STEP: u64 = 1 << 48;
+    // Same initial cells as Automata1D::new.
+    // Stochastic rules are not supported, blocks of cells could not be memoized.
+    pub fn new(rule: R, view_start: i32, view_width: u32) -> Result<HashLife1D<R>> {
+        if rule.is_stochastic() {
+            return Err(Error::StochasticRule);
+        }
+        let cells = rule.initialize();
+        let origin = -(cells.len() as i64 / 2);
+        let background = (cells[0], cells[cells.len() - 1]);
+        Ok(HashLife1D::from_cells(
+            rule, &cells, origin, background, view_start, view_width,
+        ))
+    }
+    // Same initial cells as Automata1D::with_init on an infinite lattice
+    pub fn with_init(
+        rule: R,
+        size: u32,
+        init: &InitialCondition,
+        view_start: i32,
+        view_width: u32,
+    ) -> Result<HashLife1D<R>> {
+        if rule.is_stochastic() || *init == InitialCondition::Seed {
+            return HashLife1D::new(rule, view_start, view_width);
+        }
+        if size == 0 {
+            return Err(Error::InvalidSize(size));
+        }
+        let cells = init.cells(&rule, size)?;
+        let origin = -(size as i64 / 2);
+        Ok(HashLife1D::from_cells(
+            rule,
+            &cells,
+            origin,
+            (0, 0),
+            view_start,
+            view_width,
+        ))
+    }
+    fn from_cells(
+        rule: R,
+        cells: &[u8],
+        origin: i64,
+        background: (u8, u8),
+        view_start: i32,
+        view_width: u32,
+    ) -> HashLife1D<R> {
+        let n_colors = rule.get_n_colors() as NodeId;
+        // After 2^j steps, the cells of a node of level base_level + j are known
+        // up to radius * 2^j cells from its edges, that is at most a quarter of the node.
+        let base_level = 2 + ceil_log2(rule.get_radius() as usize);
+        let level = base_level.max(ceil_log2(cells.len()));
+        let mut automata = HashLife1D {
+            rule,
+            nodes: (0..n_colors)
+                .map(|c| Node {
+                    level: 0,
+                    left: c,
+                    right: c,
+                })
+                .collect(),
+            joins: HashMap::new(),
+            results: HashMap::new(),
+            uniforms: (0..n_colors).map(|c| vec![c]).collect(),
+            base_level,
+            root: 0,
+            origin: 0,
+            background,
+            step: 0,
+            view_start,
+            view_width,
+        };
+        let width = 1usize << level;
+        let left_padding = (width - cells.len()) / 2;
+        let mut padded = vec![background.0; left_padding];
+        padded.extend_from_slice(cells);
+        padded.resize(width, background.1);
+        automata.root = automata.build(&padded);
+        automata.origin = origin - left_padding as i64;
+        automata
+    }
+    pub fn iter(&self) -> impl Iterator<Item = u8> + '_ {
+        (self.view_start..self.view_start + self.view_width as i32)
+            .map(move |x| self.get_cell(x as i64))
+    }
+    pub fn get_cell(&self, x: i64) -> u8 {
+        let mut offset = x - self.origin;
+        let mut node = self.nodes[self.root as usize];
+        if offset < 0 {
+            return self.background.0;
+        }
+        if offset >= 1 << node.level {
+            return self.background.1;
+        }
+        while node.level > 0 {
+            let half = 1 << (node.level - 1);
+            node = if offset < half {
+                self.nodes[node.left as usize]
+            } else {
+                offset -= half;
+                self.nodes[node.right as usize]
+            };
+        }
+        node.left as u8
+    }
+    // Advance by 'n_step' steps, fails without stepping beyond MAX_STEP
+    pub fn step(&mut self, n_step: u64) -> Result<()> {
+        if self
+            .step
+            .checked_add(n_step)
+            .is_none_or(|step| step > Self::MAX_STEP)
+        {
+            return Err(Error::StepOutOfRange {
+                n_step,
+                current: self.step,
+                max: Self::MAX_STEP,
+            });
+        }
+        for j in 0..64 {
+            if (n_step >> j) & 1 == 1 {
+                self.advance(j);
+            }
+        }
+        Ok(())
+    }
+    #[inline]
+    pub fn get_cur_step(&self) -> u64 {
+        self.step
+    }
+    // Number of distinct nodes currently stored
+    pub fn node_count(&self) -> usize {
+        self.nodes.len()
+    }
+    pub fn as_text(&self) -> String {
+        format!(
+            "|{}|",
+            self.iter()
+                .map(|c| self.rule.cell_to_text(&c))
+                .collect::<Vec<_>>()
+                .join("")
+        )
+    }
+    // Space-time diagram of the view, from the current step, then step 'n_step' times
+    pub fn space_time(&mut self, n_step: u32) -> Result<SpaceTime> {
+        let mut diagram = SpaceTime::new(self.view_start as i64, self.step, self.view_width);
+        for _i in 0..n_step {
+            diagram.push_row(self.iter());
+            self.step(1)?;
+        }
+        Ok(diagram)
+    }
+    pub fn as_image_buffer(&mut self, n_step: u32) -> Result<RgbImage> {
+        Ok(self.space_time(n_step)?.to_image(&self.rule))
+    }
+    pub fn as_rgb_vec(&mut self, n_step: u32) -> Result<Vec<(u8, u8, u8)>> {
+        Ok(self.space_time(n_step)?.to_rgb_vec(&self.rule))
+    }
+    pub fn as_vec(&mut self, n_step: u32) -> Result<Vec<u8>> {
+        Ok(self.space_time(n_step)?.into_cells())
+    }
+    // Advance by 2^j steps
+    fn advance(&mut self, j: u8) {
+        // The root is enlarged until its result after 2^j steps contains all the cells
+        // that are not background.
+        while self.level(self.root) < j + self.base_level || !self.is_padded(self.root) {
+            self.expand();
+        }
+        self.expand();
+        let level = self.level(self.root);
+        self.root = self.result(self.root, j);
+        self.origin += 1 << (level - 2);
+        self.step += 1 << j;
+        self.background = (
+            self.color_after(self.background.0, 1 << j),
+            self.color_after(self.background.1, 1 << j),
+        );
+        while self.level(self.root) > self.base_level && self.is_padded(self.root) {
+            self.origin += 1 << (self.level(self.root) - 2);
+            self.root = self.center(self.root);
+        }
+        if self.nodes.len() > MAX_NODES {
+            self.collect();
+        }
+    }
+    // Center half of 'node' after 2^j steps, j must be at most its level - base_level
+    fn result(&mut self, node: NodeId, j: u8) -> NodeId {
+        if let Some(&result) = self.results.get(&(node, j)) {
+            return result;
+        }
+        let level = self.level(node);
+        let result = if level == self.base_level {
+            self.base_result(node)
+        } else {
+            let Node { left, right, .. } = self.nodes[node as usize];
+            let middle = self.join(self.right(left), self.left(right));
+            // Three overlapping quarters, advanced by 2^(j-1) steps when j is the largest possible
+            // and then by 2^(j-1) steps again, or else only once by 2^j steps.
+            let (quarters, j_next) = if j == level - self.base_level {
+                (
+                    [
+                        self.result(left, j - 1),
+                        self.result(middle, j - 1),
+                        self.result(right, j - 1),
+                    ],
+                    j - 1,
+                )
+            } else {
+                (
+                    [self.center(left), self.center(middle), self.center(right)],
+                    j,
+                )
+            };
+            let left_half = self.join(quarters[0], quarters[1]);
+            let right_half = self.join(quarters[1], quarters[2]);
+            let left_result = self.result(left_half, j_next);
+            let right_result = self.result(right_half, j_next);
+            self.join(left_result, right_result)
+        };
+        self.results.insert((node, j), result);
+        result
+    }
+    // Center half of 'node' after one step, computed cell by cell
+    fn base_result(&mut self, node: NodeId) -> NodeId {
+        let mut cells = Vec::new();
+        self.flatten(node, &mut cells);
+        let radius = self.rule.get_radius() as usize;
+        let next = cells
+            .windows(2 * radius + 1)
+            .map(|w| self.rule.apply(w))
+            .collect::<Vec<_>>();
+        // next[i] is the cell i + radius
+        let start = cells.len() / 4 - radius;
+        self.build(&next[start..start + cells.len() / 2])
+    }
+    // Double the root size, keeping it centered, with background on each side
+    fn expand(&mut self) {
+        let level = self.level(self.root);
+        let Node { left, right, .. } = self.nodes[self.root as usize];
+        let left_background = self.uniform(self.background.0, level - 1);
+        let right_background = self.uniform(self.background.1, level - 1);
+        let left = self.join(left_background, left);
+        let right = self.join(right, right_background);
+        self.root = self.join(left, right);
+        self.origin -= 1 << (level - 1);
+    }
+    // Whether the first and last quarters of 'node' only contain background
+    fn is_padded(&mut self, node: NodeId) -> bool {
+        let level = self.level(node);
+        let Node { left, right, .. } = self.nodes[node as usize];
+        self.left(left) == self.uniform(self.background.0, level - 2)
+            && self.right(right) == self.uniform(self.background.1, level - 2)
+    }
+    // Color of uniform cells of 'color' after 'n_step' steps
+    fn color_after(&self, color: u8, n_step: u64) -> u8 {
+        let window = 2 * self.rule.get_radius() as usize + 1;
+        let next = |c: u8| self.rule.apply(&vec![c; window]);
+        // The colors sequence becomes periodic after at most n_colors steps
+        let mut seen = vec![None; self.rule.get_n_colors() as usize];
+        let mut color = color;
+        let mut t = 0;
+        while t < n_step {
+            if let Some(first) = seen[color as usize] {
+                for _i in 0..(n_step - t) % (t - first) {
+                    color = next(color);
+                }
+                return color;
+            }
+            seen[color as usize] = Some(t);
+            color = next(color);
+            t += 1;
+        }
+        color
+    }
+    // Keep only the nodes of the current cells
+    fn collect(&mut self) {
+        let nodes = std::mem::take(&mut self.nodes);
+        self.nodes = nodes.iter().take(self.uniforms.len()).copied().collect();
+        self.joins.clear();
+        self.results.clear();
+        for (color, uniforms) in self.uniforms.iter_mut().enumerate() {
+            uniforms.truncate(1);
+            uniforms[0] = color as NodeId;
+        }
+        let mut copied = HashMap::new();
+        self.root = self.copy(&nodes, self.root, &mut copied);
+    }
+    fn copy(
+        &mut self,
+        nodes: &[Node],
+        node: NodeId,
+        copied: &mut HashMap<NodeId, NodeId>,
+    ) -> NodeId {
+        let Node { level, left, right } = nodes[node as usize];
+        if level == 0 {
+            return node;
+        }
+        if let Some(&id) = copied.get(&node) {
+            return id;
+        }
+        let left = self.copy(nodes, left, copied);
+        let right = self.copy(nodes, right, copied);
+        let id = self.join(left, right);
+        copied.insert(node, id);
+        id
+    }
+    fn join(&mut self, left: NodeId, right: NodeId) -> NodeId {
+        if let Some(&id) = self.joins.get(&(left, right)) {
+            return id;
+        }
+        let id = self.nodes.len() as NodeId;
+        self.nodes.push(Node {
+            level: self.level(left) + 1,
+            left,
+            right,
+        });
+        self.joins.insert((left, right), id);
+        id
+    }
+    fn uniform(&mut self, color: u8, level: u8) -> NodeId {
+        while self.uniforms[color as usize].len() <= level as usize {
+            let last = *self.uniforms[color as usize].last().unwrap();
+            let node = self.join(last, last);
+            self.uniforms[color as usize].push(node);
+        }
+        self.uniforms[color as usize][level as usize]
+    }
+    // Node of the cells in the center half of 'node'
+    fn center(&mut self, node: NodeId) -> NodeId {
+        let Node { left, right, .. } = self.nodes[node as usize];
+        self.join(self.right(left), self.left(right))
+    }
+    // Node of 'cells', whose length is a power of 2
+    fn build(&mut self, cells: &[u8]) -> NodeId {
+        if cells.len() == 1 {
+            return cells[0] as NodeId;
+        }
+        let half = cells.len() / 2;
+        let left = self.build(&cells[..half]);
+        let right = self.build(&cells[half..]);
+        self.join(left, right)
+    }
+    fn flatten(&self, node: NodeId, cells: &mut Vec<u8>) {
+        let Node { level, left, right } = self.nodes[node as usize];
+        if level == 0 {
+            cells.push(left as u8);
+        } else {
+            self.flatten(left, cells);
+            self.flatten(right, cells);
+        }
+    }
+    #[inline]
+    fn level(&self, node: NodeId) -> u8 {
+        self.nodes[node as usize].level
+    }
+    #[inline]
+    fn left(&self, node: NodeId) -> NodeId {
+        self.nodes[node as usize].left
+    }
+    #[inline]
+    fn right(&self, node: NodeId) -> NodeId {
+        self.nodes[node as usize].right
+    }
+}
+fn ceil_log2(n: usize) -> u8 {
+    (usize::BITS - n.saturating_sub(1).leading_zeros()) as u8
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::automata::Automata1D;
+    use crate::rules::Rule1D;
+    use crate::stochastic::StochasticRule;
+    use num_bigint::BigUint;
+
+    #[test]
+    fn hashlife_matches_automata() {
+        let check = |rule: fn() -> Rule1D, init: &InitialCondition| {
+            let mut automata =
+                Automata1D::with_init(rule(), crate::Boundary::Infinite, 30, init, -200, 400)
+                    .unwrap();
+            let mut hashlife = HashLife1D::with_init(rule(), 30, init, -200, 400).unwrap();
+            for n_step in [1, 2, 5, 64, 100].iter() {
+                automata.step(*n_step);
+                hashlife.step(*n_step as u64).unwrap();
+                assert_eq!(hashlife.as_text(), automata.as_text());
+                assert_eq!(hashlife.get_cur_step(), automata.get_cur_step() as u64);
+            }
+        };
+        let random = InitialCondition::Random {
+            density: 0.5,
+            seed: 4,
+        };
+        for init in [InitialCondition::Seed, random.clone()].iter() {
+            check(|| Rule1D::new(2, 30), init);
+            check(|| Rule1D::new(2, 110), init);
+            check(|| Rule1D::new(2, 127), init);
+            check(|| Rule1D::new(3, 40327), init);
+            check(|| Rule1D::new(4, 16673), init);
+            check(
+                || Rule1D::with_radius(2, 2, &BigUint::from(1436965290u32)),
+                init,
+            );
+            check(
+                || Rule1D::with_radius(2, 3, &BigUint::from(u128::MAX / 7)),
+                init,
+            );
+        }
+        // Random new cells cannot be memoized
+        let noisy = || StochasticRule::noisy(&Rule1D::new(2, 30), 0.1).unwrap();
+        assert!(matches!(
+            HashLife1D::new(noisy(), -5, 11),
+            Err(Error::StochasticRule)
+        ));
+        assert!(HashLife1D::with_init(noisy(), 30, &random, -5, 11).is_err());
+    }
+    #[test]
+    fn hashlife_jumps_far() {
+        // Rule 90 from a single cell draws the Sierpinski triangle,
+        // at step 2^k only the two cells at -2^k and 2^k are black.
+        let mut hashlife = HashLife1D::new(Rule1D::new(2, 90), -5, 11).unwrap();
+        hashlife.step(1 << 40).unwrap();
+        assert_eq!(hashlife.get_cur_step(), 1 << 40);
+        assert_eq!(hashlife.get_cell(1 << 40), 1);
+        assert_eq!(hashlife.get_cell(-(1 << 40)), 1);
+        assert_eq!(hashlife.get_cell((1 << 40) + 1), 0);
+        assert_eq!(hashlife.get_cell((1 << 40) - 2), 0);
+        assert_eq!(hashlife.as_text(), "|           |");
+        assert!(hashlife.node_count() < 10_000);
+        // Background alternates on rule 1, 000 gives 1 and 111 gives 0
+        let mut hashlife = HashLife1D::new(Rule1D::new(2, 1), -5, 11).unwrap();
+        let mut automata = Automata1D::new(Rule1D::new(2, 1), -5, 11);
+        hashlife.step(1_000_001).unwrap();
+        automata.step(1_000_001);
+        assert_eq!(hashlife.as_text(), automata.as_text());
+        assert_eq!(hashlife.get_cell(1 << 50), 1);
+        // Steps stop at MAX_STEP, without moving
+        let mut hashlife = HashLife1D::new(
+            Rule1D::with_radius(2, 2, &BigUint::from(0x96696996u32)),
+            -5,
+            11,
+        )
+        .unwrap();
+        assert!(hashlife.step(u64::MAX).is_err());
+        assert!(hashlife.step(HashLife1D::<Rule1D>::MAX_STEP + 1).is_err());
+        assert_eq!(hashlife.get_cur_step(), 0);
+        hashlife.step(HashLife1D::<Rule1D>::MAX_STEP).unwrap();
+        assert_eq!(hashlife.get_cur_step(), HashLife1D::<Rule1D>::MAX_STEP);
+        assert!(hashlife.step(1).is_err());
+        assert!(hashlife.as_vec(1).is_err());
+    }
+    #[test]
+    fn hashlife_collect_keeps_cells() {
+        let mut hashlife = HashLife1D::new(Rule1D::new(2, 30), -100, 201).unwrap();
+        let mut automata = Automata1D::new(Rule1D::new(2, 30), -100, 201);
+        hashlife.step(300).unwrap();
+        automata.step(300);
+        let count = hashlife.node_count();
+        hashlife.collect();
+        assert!(hashlife.node_count() < count);
+        assert_eq!(hashlife.as_text(), automata.as_text());
+        hashlife.step(50).unwrap();
+        automata.step(50);
+        assert_eq!(hashlife.as_text(), automata.as_text());
+    }
+}
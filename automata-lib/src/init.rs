@@ -0,0 +1,336 @@
+use crate::cellular_rule::CellularRule;
+use crate::error::{Error, Result};
+use rand::{Rng, SeedableRng};
+use rand_pcg::Pcg32;
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+// Initial cells of an automata.
+// Cells are generated for a region of 'size' cells, centered on cell 0 for an infinite lattice,
+// or covering the whole finite lattice.
+#[derive(Debug, Clone, PartialEq)]
+pub enum InitialCondition {
+    // A single cell of the last color at the center, on a background of 0
+    Seed,
+    // Each cell has a non-zero color with probability 'density', uniformly chosen among the non-zero colors.
+    // The same seed always gives the same cells.
+    Random {
+        density: f64,
+        seed: u64,
+    },
+    // Literal cells, centered on a background of 0
+    Pattern(Vec<u8>),
+    // The 'background' cells repeated, with 'defect' replacing the background at the center
+    Defect {
+        background: Vec<u8>,
+        defect: Vec<u8>,
+    },
+    // First row of a PNG image, or first line of a text file, read as a pattern.
+    // Text lines are either hexadecimal colors or the cell texts of the rule, as printed by the CLI.
+    File(PathBuf),
+}
+impl InitialCondition {
+    pub fn cells<R: CellularRule>(&self, rule: &R, size: u32) -> Result<Vec<u8>> {
+        let n_colors = rule.get_n_colors();
+        let size = size as usize;
+        let mut cells = vec![0; size];
+        match self {
+            InitialCondition::Seed if size == 0 => return Err(Error::InvalidSize(0)),
+            InitialCondition::Seed => cells[size / 2] = n_colors - 1,
+            InitialCondition::Random { density, seed } => {
+                if !(0.0..=1.0).contains(density) {
+                    return Err(Error::InvalidDensity(*density));
+                }
+                let mut rng = Pcg32::seed_from_u64(*seed);
+                for cell in cells.iter_mut() {
+                    if rng.gen_bool(*density) {
+                        *cell = rng.gen_range(1, n_colors);
+                    }
+                }
+            }
+            InitialCondition::Pattern(pattern) => {
+                place(&mut cells, pattern, n_colors)?;
+            }
+            InitialCondition::Defect { background, defect } => {
+                if background.is_empty() {
+                    return Err(Error::InvalidPattern(String::from("empty background")));
+                }
+                check_colors(background, n_colors)?;
+                for (i, cell) in cells.iter_mut().enumerate() {
+                    *cell = background[i % background.len()];
+                }
+                place(&mut cells, defect, n_colors)?;
+            }
+            InitialCondition::File(path) => {
+                let pattern = load(path, rule)?;
+                place(&mut cells, &pattern, n_colors)?;
+            }
+        }
+        Ok(cells)
+    }
+}
+// Copy 'pattern' at the center of 'cells'
+fn place(cells: &mut [u8], pattern: &[u8], n_colors: u8) -> Result<()> {
+    if pattern.len() > cells.len() {
+        return Err(Error::InvalidPattern(format!(
+            "{} cells do not fit in a lattice of {} cells",
+            pattern.len(),
+            cells.len()
+        )));
+    }
+    check_colors(pattern, n_colors)?;
+    let start = (cells.len() - pattern.len()) / 2;
+    cells[start..start + pattern.len()].copy_from_slice(pattern);
+    Ok(())
+}
+fn check_colors(pattern: &[u8], n_colors: u8) -> Result<()> {
+    match pattern.iter().find(|&&c| c >= n_colors) {
+        Some(c) => Err(Error::InvalidPattern(format!(
+            "color {} is not a color of the rule",
+            c
+        ))),
+        None => Ok(()),
+    }
+}
+// Parse a string of hexadecimal colors, like "0120012"
+fn parse_pattern(s: &str) -> Option<Vec<u8>> {
+    s.chars().map(|c| c.to_digit(16).map(|d| d as u8)).collect()
+}
+fn load<R: CellularRule>(path: &Path, rule: &R) -> Result<Vec<u8>> {
+    let n_colors = rule.get_n_colors();
+    let is_png = path
+        .extension()
+        .is_some_and(|ext| ext.eq_ignore_ascii_case("png"));
+    if is_png {
+        // Each pixel is the color of the rule with the closest RGB value
+        let image = image::open(path)?.into_rgb8();
+        let palette = (0..n_colors)
+            .map(|c| rule.cell_to_rgb(&c))
+            .collect::<Vec<_>>();
+        let distance = |(r, g, b): (u8, u8, u8), p: &image::Rgb<u8>| {
+            let d = |x: u8, y: u8| (x as i32 - y as i32).pow(2);
+            d(r, p[0]) + d(g, p[1]) + d(b, p[2])
+        };
+        Ok((0..image.width())
+            .map(|x| {
+                let pixel = image.get_pixel(x, 0);
+                (0..n_colors)
+                    .min_by_key(|&c| distance(palette[c as usize], pixel))
+                    .unwrap()
+            })
+            .collect())
+    } else {
+        let text = std::fs::read_to_string(path)?;
+        let line = text.lines().find(|l| !l.trim().is_empty()).unwrap_or("");
+        let line = line.trim_start_matches('|').trim_end_matches('|');
+        let texts = (0..n_colors)
+            .map(|c| rule.cell_to_text(&c))
+            .collect::<Vec<_>>();
+        line.chars()
+            .map(|ch| {
+                let text = ch.to_string();
+                match texts.iter().position(|t| *t == text) {
+                    Some(c) => Some(c as u8),
+                    None => ch.to_digit(16).map(|d| d as u8),
+                }
+                .ok_or_else(|| Error::InvalidPattern(format!("unknown cell '{}'", ch)))
+            })
+            .collect()
+    }
+}
+impl fmt::Display for InitialCondition {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let hex = |cells: &[u8]| {
+            cells
+                .iter()
+                .map(|&c| std::char::from_digit(c as u32, 16).unwrap())
+                .collect::<String>()
+        };
+        match self {
+            InitialCondition::Seed => write!(f, "seed"),
+            InitialCondition::Random { density, seed } => {
+                write!(f, "random:{}:{}", density, seed)
+            }
+            InitialCondition::Pattern(pattern) => write!(f, "pattern:{}", hex(pattern)),
+            InitialCondition::Defect { background, defect } => {
+                write!(f, "defect:{}:{}", hex(background), hex(defect))
+            }
+            InitialCondition::File(path) => write!(f, "file:{}", path.display()),
+        }
+    }
+}
+// Parse "seed", "random[:<density>[:<seed>]]", "[pattern:]<cells>", "defect:<background>:<cells>" or "file:<path>".
+// Cells are hexadecimal colors, random density defaults to 0.5 and seed to 0.
+impl FromStr for InitialCondition {
+    type Err = String;
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        let err = || format!("Unknown initial condition '{}'", s);
+        let mut fields = s.splitn(2, ':');
+        let name = fields.next().unwrap();
+        let args = fields.next();
+        match (name, args) {
+            ("seed", None) => Ok(InitialCondition::Seed),
+            ("random", _) => {
+                let mut args = args.unwrap_or("").split(':');
+                let density = match args.next() {
+                    Some("") | None => 0.5,
+                    Some(d) => d.parse::<f64>().map_err(|_| err())?,
+                };
+                let seed = match args.next() {
+                    Some(v) => v.parse::<u64>().map_err(|_| err())?,
+                    None => 0,
+                };
+                match args.next() {
+                    None => Ok(InitialCondition::Random { density, seed }),
+                    Some(_) => Err(err()),
+                }
+            }
+            ("pattern", Some(cells)) => parse_pattern(cells)
+                .map(InitialCondition::Pattern)
+                .ok_or_else(err),
+            ("defect", Some(args)) => {
+                let mut args = args.split(':');
+                match (
+                    args.next().and_then(parse_pattern),
+                    args.next().and_then(parse_pattern),
+                    args.next(),
+                ) {
+                    (Some(background), Some(defect), None) => {
+                        Ok(InitialCondition::Defect { background, defect })
+                    }
+                    _ => Err(err()),
+                }
+            }
+            ("file", Some(path)) => Ok(InitialCondition::File(PathBuf::from(path))),
+            (cells, None) => parse_pattern(cells)
+                .filter(|p| !p.is_empty())
+                .map(InitialCondition::Pattern)
+                .ok_or_else(err),
+            _ => Err(err()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rules::Rule1D;
+
+    #[test]
+    fn init_cells_works() {
+        let rule = Rule1D::new(3, 0);
+        assert_eq!(
+            InitialCondition::Seed.cells(&rule, 5).unwrap(),
+            vec![0, 0, 2, 0, 0]
+        );
+        let pattern = InitialCondition::Pattern(vec![1, 2, 1]);
+        assert_eq!(pattern.cells(&rule, 7).unwrap(), vec![0, 0, 1, 2, 1, 0, 0]);
+        assert!(matches!(
+            pattern.cells(&rule, 2),
+            Err(Error::InvalidPattern(_))
+        ));
+        let defect = InitialCondition::Defect {
+            background: vec![0, 1],
+            defect: vec![2],
+        };
+        assert_eq!(
+            defect.cells(&rule, 8).unwrap(),
+            vec![0, 1, 0, 2, 0, 1, 0, 1]
+        );
+        assert!(matches!(
+            InitialCondition::Pattern(vec![3]).cells(&rule, 3),
+            Err(Error::InvalidPattern(_))
+        ));
+    }
+    #[test]
+    fn init_random_works() {
+        let rule = Rule1D::new(4, 0);
+        let random = |density, seed| {
+            InitialCondition::Random { density, seed }
+                .cells(&rule, 10000)
+                .unwrap()
+        };
+        // Reproducible from the seed
+        assert_eq!(random(0.3, 42), random(0.3, 42));
+        assert_ne!(random(0.3, 42), random(0.3, 43));
+        let cells = random(0.3, 42);
+        let active = cells.iter().filter(|&&c| c != 0).count();
+        assert!(active > 2800 && active < 3200);
+        for color in 1..4 {
+            assert!(cells.contains(&color));
+        }
+        assert!(random(0.0, 1).iter().all(|&c| c == 0));
+        assert!(matches!(
+            InitialCondition::Random {
+                density: 1.5,
+                seed: 0
+            }
+            .cells(&rule, 3),
+            Err(Error::InvalidDensity(_))
+        ));
+    }
+    #[test]
+    fn init_parse() {
+        for init in [
+            InitialCondition::Seed,
+            InitialCondition::Random {
+                density: 0.25,
+                seed: 7,
+            },
+            InitialCondition::Pattern(vec![0, 1, 2, 0, 0, 1, 2]),
+            InitialCondition::Defect {
+                background: vec![0, 1],
+                defect: vec![1, 1],
+            },
+            InitialCondition::File(PathBuf::from("row.png")),
+        ]
+        .iter()
+        {
+            assert_eq!(
+                init.to_string().parse::<InitialCondition>().as_ref(),
+                Ok(init)
+            );
+        }
+        assert_eq!(
+            "0120012".parse::<InitialCondition>(),
+            Ok(InitialCondition::Pattern(vec![0, 1, 2, 0, 0, 1, 2]))
+        );
+        assert_eq!(
+            "random".parse::<InitialCondition>(),
+            Ok(InitialCondition::Random {
+                density: 0.5,
+                seed: 0
+            })
+        );
+        assert!("random:x".parse::<InitialCondition>().is_err());
+        assert!("defect:01".parse::<InitialCondition>().is_err());
+        assert!("seeds".parse::<InitialCondition>().is_err());
+    }
+    #[test]
+    fn init_file_works() {
+        let rule = Rule1D::new(3, 0);
+        let dir = std::env::temp_dir();
+        let text_file = dir.join(format!("automata_init_{}.txt", std::process::id()));
+        std::fs::write(&text_file, "\n| +*  |\n|     |\n").unwrap();
+        let init = InitialCondition::File(text_file.clone());
+        assert_eq!(init.cells(&rule, 7).unwrap(), vec![0, 0, 1, 2, 0, 0, 0]);
+        std::fs::write(&text_file, "0120").unwrap();
+        assert_eq!(init.cells(&rule, 4).unwrap(), vec![0, 1, 2, 0]);
+        std::fs::remove_file(&text_file).unwrap();
+
+        let png_file = dir.join(format!("automata_init_{}.png", std::process::id()));
+        let mut image = image::RgbImage::new(3, 2);
+        image.put_pixel(0, 0, image::Rgb([0, 0, 0]));
+        image.put_pixel(1, 0, image::Rgb([255, 255, 255]));
+        image.put_pixel(2, 0, image::Rgb([0, 0, 180]));
+        image.save(&png_file).unwrap();
+        let init = InitialCondition::File(png_file.clone());
+        assert_eq!(init.cells(&rule, 3).unwrap(), vec![2, 0, 1]);
+        std::fs::remove_file(&png_file).unwrap();
+        assert!(matches!(
+            InitialCondition::File(PathBuf::from("/nonexistent/row.txt")).cells(&rule, 3),
+            Err(Error::Io(_))
+        ));
+    }
+}
@@ -1,5 +1,38 @@
+mod analysis;
 mod automata;
+mod boundary;
+mod cellular_rule;
+mod classify;
+mod cycle;
+mod damage;
+mod debruijn;
+mod error;
+mod hashlife;
+mod init;
+mod linear;
+mod packed;
+mod preimage;
 mod rules;
+mod second_order;
+mod spacetime;
+mod stochastic;
+mod update;
 
+pub use analysis::RunStats;
 pub use automata::*;
+pub use boundary::Boundary;
+pub use cellular_rule::*;
+pub use classify::{ClassSignals, Classification, Classifier, WolframClass};
+pub use cycle::Cycle;
+pub use damage::Damage;
+pub use debruijn::DeBruijn;
+pub use error::{Error, Result};
+pub use hashlife::HashLife1D;
+pub use init::InitialCondition;
 pub use rules::*;
+pub use second_order::SecondOrderAutomata1D;
+pub use spacetime::SpaceTime;
+pub use stochastic::StochasticRule;
+pub use update::UpdateScheme;
+
+pub use num_bigint::BigUint;
@@ -0,0 +1,175 @@
+// Closed form evolution of linear rules, whose new cell is sum(a_i * window_i) mod k.
+// One step multiplies the cells by the polynomial P(z) = sum(a_i * z^(i-radius)),
+// so that 't' steps multiply them by P(z)^t.
+// Modulo a prime p, P(z)^p = P(z^p), this is Lucas' theorem in polynomial form, so that with
+// t = sum(d_m * p^m), P(z)^t = prod(P(z^(p^m))^d_m): a few steps with windows spread by p^m.
+
+pub(crate) fn is_prime(k: u8) -> bool {
+    k >= 2
+        && (2..k)
+            .take_while(|d| d * d <= k)
+            .all(|d| !k.is_multiple_of(d))
+}
+
+// Apply 't' steps to the cells of a ring, whose cell -1 is its last cell.
+pub(crate) fn ring_jump(coefs: &[u8], n_colors: u8, ring: &mut Vec<u8>, t: u64) {
+    let n = ring.len() as u64;
+    let mut next = vec![0; ring.len()];
+    if is_prime(n_colors) {
+        let p = n_colors as u64;
+        let mut t = t;
+        let mut stride = 1 % n;
+        while t > 0 {
+            for _d in 0..t % p {
+                ring_apply(coefs, n_colors, ring, &mut next, stride);
+                std::mem::swap(ring, &mut next);
+            }
+            stride = stride * p % n;
+            t /= p;
+        }
+    } else if n * 2 * (64 - t.leading_zeros() as u64) < coefs.len() as u64 * t {
+        // Without the Frobenius shortcut, the step polynomial is raised to 't' on the ring,
+        // which is worth it once 't' is large compared to the ring size.
+        let step = ring_polynomial(coefs, n_colors, n, 1);
+        let poly = ring_pow(&step, n_colors, t);
+        for (j, cell) in next.iter_mut().enumerate() {
+            let sum = poly
+                .iter()
+                .enumerate()
+                .map(|(m, &c)| c as u32 * ring[(j + m) % n as usize] as u32)
+                .sum::<u32>();
+            *cell = (sum % n_colors as u32) as u8;
+        }
+        std::mem::swap(ring, &mut next);
+    } else {
+        for _j in 0..t {
+            ring_apply(coefs, n_colors, ring, &mut next, 1);
+            std::mem::swap(ring, &mut next);
+        }
+    }
+}
+
+// Coefficients c such that one step with windows spread by 'stride' is next(j) = sum(c_m * ring(j+m))
+fn ring_polynomial(coefs: &[u8], n_colors: u8, n: u64, stride: u64) -> Vec<u8> {
+    let radius = (coefs.len() / 2) as i64;
+    let mut poly = vec![0u8; n as usize];
+    for (i, &a) in coefs.iter().enumerate() {
+        let m = ((i as i64 - radius) * stride as i64).rem_euclid(n as i64) as usize;
+        poly[m] = (poly[m] + a) % n_colors;
+    }
+    poly
+}
+fn ring_apply(coefs: &[u8], n_colors: u8, ring: &[u8], next: &mut [u8], stride: u64) {
+    let n = ring.len();
+    let terms = ring_polynomial(coefs, n_colors, n as u64, stride)
+        .into_iter()
+        .enumerate()
+        .filter(|&(_m, c)| c != 0)
+        .collect::<Vec<_>>();
+    for (j, cell) in next.iter_mut().enumerate() {
+        let sum = terms
+            .iter()
+            .map(|&(m, c)| c as u32 * ring[(j + m) % n] as u32)
+            .sum::<u32>();
+        *cell = (sum % n_colors as u32) as u8;
+    }
+}
+// Product of two ring polynomials, modulo z^n - 1
+fn ring_mul(a: &[u8], b: &[u8], n_colors: u8) -> Vec<u8> {
+    let n = a.len();
+    let mut prod = vec![0u32; n];
+    for (i, &x) in a.iter().enumerate().filter(|&(_i, &x)| x != 0) {
+        for (j, &y) in b.iter().enumerate() {
+            prod[(i + j) % n] += x as u32 * y as u32;
+        }
+        for c in prod.iter_mut() {
+            *c %= n_colors as u32;
+        }
+    }
+    prod.into_iter().map(|c| c as u8).collect()
+}
+fn ring_pow(poly: &[u8], n_colors: u8, t: u64) -> Vec<u8> {
+    let mut result = vec![0u8; poly.len()];
+    result[0] = 1;
+    let mut square = poly.to_vec();
+    let mut t = t;
+    while t > 0 {
+        if t & 1 == 1 {
+            result = ring_mul(&result, &square, n_colors);
+        }
+        t >>= 1;
+        if t > 0 {
+            square = ring_mul(&square, &square, n_colors);
+        }
+    }
+    result
+}
+
+// Apply 't' steps to the cells of an infinite lattice, whose edges are repeated beyond its ends.
+// The number of colors must be prime. The returned cells have radius*t more cells on each side.
+pub(crate) fn infinite_jump(coefs: &[u8], n_colors: u8, cells: &[u8], t: u64) -> Vec<u8> {
+    let k = n_colors as u32;
+    let radius = coefs.len() / 2;
+    let grow = radius * t as usize;
+    let (mut left, mut right) = (cells[0], cells[cells.len() - 1]);
+    // The background is uniform, so each step multiplies it by the sum of the coefficients
+    let scale = coefs.iter().map(|&a| a as u32).sum::<u32>() % k;
+    let mut cur = vec![left; grow];
+    cur.extend_from_slice(cells);
+    cur.extend(std::iter::repeat_n(right, grow));
+    let len = cur.len() as i64;
+    let mut next = vec![0; cur.len()];
+    let p = n_colors as u64;
+    let mut t = t;
+    let mut stride = 1i64;
+    while t > 0 {
+        for _d in 0..t % p {
+            let offsets = coefs
+                .iter()
+                .enumerate()
+                .filter(|&(_i, &a)| a != 0)
+                .map(|(i, &a)| ((i as i64 - radius as i64) * stride, a as u32))
+                .collect::<Vec<_>>();
+            for (j, cell) in next.iter_mut().enumerate() {
+                let sum = offsets
+                    .iter()
+                    .map(|&(offset, a)| {
+                        let idx = j as i64 + offset;
+                        let c = if idx < 0 {
+                            left
+                        } else if idx >= len {
+                            right
+                        } else {
+                            cur[idx as usize]
+                        };
+                        a * c as u32
+                    })
+                    .sum::<u32>();
+                *cell = (sum % k) as u8;
+            }
+            std::mem::swap(&mut cur, &mut next);
+            left = (scale * left as u32 % k) as u8;
+            right = (scale * right as u32 % k) as u8;
+        }
+        stride *= p as i64;
+        t /= p;
+    }
+    cur
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ring_pow_works() {
+        assert!(is_prime(2) && is_prime(13) && !is_prime(1) && !is_prime(9));
+        // Rule 60 on a ring of 4 cells: next(j) = cell(j-1) + cell(j), its square skips a cell mod 2
+        let step = ring_polynomial(&[1, 1, 0], 2, 4, 1);
+        assert_eq!(step, vec![1, 0, 0, 1]);
+        assert_eq!(ring_pow(&step, 2, 2), vec![1, 0, 1, 0]);
+        assert_eq!(ring_pow(&step, 2, 2), ring_polynomial(&[1, 1, 0], 2, 4, 2));
+        // Modulo 4, the square keeps the middle term
+        assert_eq!(ring_pow(&step, 4, 2), vec![1, 0, 1, 2]);
+    }
+}
@@ -0,0 +1,297 @@
+use crate::boundary::Boundary;
+use crate::cellular_rule::CellularRule;
+use std::collections::HashMap;
+
+// Number of words computed by each parallel task, tests use small chunks to check their edges
+#[cfg(feature = "parallel")]
+const PAR_CHUNK_WORDS: usize = if cfg!(test) { 3 } else { 1 << 10 };
+
+// Largest radius evaluated on packed cells, the rule diagram can have up to 2^(2*radius+1) nodes.
+const MAX_PACKED_RADIUS: u8 = 3;
+
+// Cells of a 2 colors automata, packed 64 per word, cell 'i' is bit i%64 of word i/64.
+// Bits of the last word beyond 'len' are always 0.
+#[derive(Clone)]
+pub(crate) struct PackedCells {
+    words: Vec<u64>,
+    len: usize,
+}
+impl PackedCells {
+    pub(crate) fn pack(cells: &[u8]) -> PackedCells {
+        let words = cells
+            .chunks(64)
+            .map(|chunk| {
+                chunk
+                    .iter()
+                    .enumerate()
+                    .fold(0u64, |word, (i, &c)| word | ((c as u64) << i))
+            })
+            .collect();
+        PackedCells {
+            words,
+            len: cells.len(),
+        }
+    }
+    pub(crate) fn unpack(&self, cells: &mut Vec<u8>) {
+        cells.clear();
+        cells.extend((0..self.len).map(|i| self.get(i)));
+    }
+    #[inline]
+    pub(crate) fn len(&self) -> usize {
+        self.len
+    }
+    #[inline]
+    pub(crate) fn get(&self, i: usize) -> u8 {
+        ((self.words[i / 64] >> (i % 64)) & 1) as u8
+    }
+    // The 64 cells starting at cell 'start', cells outside of the lattice are seen through 'boundary'
+    #[inline]
+    fn word_at(&self, start: i64, boundary: Boundary) -> u64 {
+        if start >= 0 && start as usize + 64 <= self.len {
+            let (j, b) = (start as usize / 64, start as usize % 64);
+            if b == 0 {
+                self.words[j]
+            } else {
+                (self.words[j] >> b) | (self.words[j + 1] << (64 - b))
+            }
+        } else {
+            (0..64).fold(0u64, |word, i| {
+                let cell = boundary.get_with(self.len, start + i, |idx| self.get(idx));
+                word | ((cell as u64) << i)
+            })
+        }
+    }
+    // Drop whole words of background on each side, beyond one word of padding.
+    // Return the number of cells dropped on the left.
+    fn trim(&mut self) -> usize {
+        let infinite = Boundary::Infinite;
+        let mut dropped = 0;
+        let left = if self.words[0] & 1 == 1 { !0 } else { 0 };
+        while self.len >= 3 * 64
+            && self.words[dropped / 64] == left
+            && self.words[dropped / 64 + 1] == left
+        {
+            dropped += 64;
+            self.len -= 64;
+        }
+        self.words.drain(..dropped / 64);
+        let right = if self.get(self.len - 1) == 1 { !0 } else { 0 };
+        while self.len >= 3 * 64
+            && self.word_at(self.len as i64 - 64, infinite) == right
+            && self.word_at(self.len as i64 - 128, infinite) == right
+        {
+            self.len -= 64;
+        }
+        self.words.truncate(self.len.div_ceil(64));
+        self.clear_tail();
+        dropped
+    }
+    fn clear_tail(&mut self) {
+        if !self.len.is_multiple_of(64) {
+            let last = self.words.len() - 1;
+            self.words[last] &= (1u64 << (self.len % 64)) - 1;
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+enum Node {
+    Const(u64),
+    // Cell 'var' of the window selects between the 'lo' and 'hi' nodes
+    Mux { var: usize, lo: usize, hi: usize },
+}
+// 2 colors rule evaluated on 64 cells at once, as the reduced binary decision diagram
+// of its output over the window cells.
+// Nodes are ordered so that children come before their parents.
+#[derive(Clone)]
+pub(crate) struct PackedRule {
+    radius: usize,
+    nodes: Vec<Node>,
+    root: usize,
+}
+impl PackedRule {
+    // None when the rule is not a 2 colors rule with a small enough radius
+    pub(crate) fn new<R: CellularRule>(rule: &R) -> Option<PackedRule> {
+        if rule.get_n_colors() != 2 || rule.get_radius() > MAX_PACKED_RADIUS || rule.is_stochastic()
+        {
+            return None;
+        }
+        let radius = rule.get_radius() as usize;
+        let window = 2 * radius + 1;
+        // Output of each window, the leftmost cell is the most significant bit of the index
+        let table = (0..1usize << window)
+            .map(|idx| {
+                let cells = (0..window)
+                    .map(|i| ((idx >> (window - 1 - i)) & 1) as u8)
+                    .collect::<Vec<_>>();
+                rule.apply(&cells)
+            })
+            .collect::<Vec<_>>();
+        let mut nodes = vec![Node::Const(0), Node::Const(!0)];
+        let mut unique = HashMap::new();
+        let root = build(&table, 0, &mut nodes, &mut unique);
+        nodes.truncate(root + 1);
+        Some(PackedRule {
+            radius,
+            nodes,
+            root,
+        })
+    }
+    // One step of 'cells' into 'next', whose words are computed by 'step_words'.
+    // On an infinite lattice, 'next' has 'radius' more cells on each side.
+    fn step(
+        &self,
+        cells: &PackedCells,
+        boundary: Boundary,
+        next: &mut PackedCells,
+        step_words: StepWords,
+    ) {
+        let len = if boundary.is_finite() {
+            cells.len
+        } else {
+            cells.len + 2 * self.radius
+        };
+        next.words.clear();
+        next.words.resize(len.div_ceil(64), 0);
+        step_words(self, cells, boundary, 0, &mut next.words);
+        next.len = len;
+        next.clear_tail();
+    }
+    // Compute the words of the next cells, starting at word 'first'
+    pub(crate) fn step_words(
+        &self,
+        cells: &PackedCells,
+        boundary: Boundary,
+        first: usize,
+        words: &mut [u64],
+    ) {
+        let radius = self.radius as i64;
+        let shift = if boundary.is_finite() { 0 } else { radius };
+        let mut vars = vec![0u64; 2 * self.radius + 1];
+        let mut values = vec![0u64; self.nodes.len()];
+        for (q, word) in words.iter_mut().enumerate() {
+            let center = 64 * (first + q) as i64 - shift;
+            for (k, var) in vars.iter_mut().enumerate() {
+                *var = cells.word_at(center + k as i64 - radius, boundary);
+            }
+            *word = self.apply(&vars, &mut values);
+        }
+    }
+    // Same as step_words, with the words split in chunks computed by the threads of the rayon pool
+    #[cfg(feature = "parallel")]
+    pub(crate) fn par_step_words(
+        &self,
+        cells: &PackedCells,
+        boundary: Boundary,
+        first: usize,
+        words: &mut [u64],
+    ) {
+        use rayon::prelude::*;
+        words
+            .par_chunks_mut(PAR_CHUNK_WORDS)
+            .enumerate()
+            .for_each(|(k, chunk)| {
+                self.step_words(cells, boundary, first + k * PAR_CHUNK_WORDS, chunk)
+            });
+    }
+    #[inline]
+    fn apply(&self, vars: &[u64], values: &mut [u64]) -> u64 {
+        for (i, node) in self.nodes.iter().enumerate() {
+            values[i] = match *node {
+                Node::Const(c) => c,
+                Node::Mux { var, lo, hi } => (vars[var] & values[hi]) | (!vars[var] & values[lo]),
+            };
+        }
+        values[self.root]
+    }
+}
+// Node computing 'table', the outputs of the windows whose first 'var' cells are fixed
+fn build(
+    table: &[u8],
+    var: usize,
+    nodes: &mut Vec<Node>,
+    unique: &mut HashMap<(usize, usize, usize), usize>,
+) -> usize {
+    if table.iter().all(|&c| c == table[0]) {
+        return table[0] as usize;
+    }
+    let half = table.len() / 2;
+    let lo = build(&table[..half], var + 1, nodes, unique);
+    let hi = build(&table[half..], var + 1, nodes, unique);
+    if lo == hi {
+        return lo;
+    }
+    *unique.entry((var, lo, hi)).or_insert_with(|| {
+        nodes.push(Node::Mux { var, lo, hi });
+        nodes.len() - 1
+    })
+}
+pub(crate) type StepWords = fn(&PackedRule, &PackedCells, Boundary, usize, &mut [u64]);
+
+// Run 'n_step' steps of 'cells' with the packed rule.
+// Return the shift of the first cell, that is negative on an infinite lattice as it grows to the left.
+pub(crate) fn step_packed(
+    rule: &PackedRule,
+    cells: &mut PackedCells,
+    boundary: Boundary,
+    n_step: u32,
+    step_words: StepWords,
+) -> i64 {
+    let mut next = PackedCells {
+        words: Vec::new(),
+        len: 0,
+    };
+    let mut shift = 0i64;
+    for _j in 0..n_step {
+        rule.step(cells, boundary, &mut next, step_words);
+        std::mem::swap(cells, &mut next);
+        if !boundary.is_finite() {
+            shift += cells.trim() as i64 - rule.radius as i64;
+        }
+    }
+    shift
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rules::Rule1D;
+    use num_bigint::BigUint;
+
+    #[test]
+    fn packed_cells_works() {
+        let cells = (0..150).map(|i| (i % 3 == 0) as u8).collect::<Vec<_>>();
+        let packed = PackedCells::pack(&cells);
+        let mut unpacked = Vec::new();
+        packed.unpack(&mut unpacked);
+        assert_eq!(unpacked, cells);
+        // Word starting before the first cell, through the boundary
+        let word = packed.word_at(-2, Boundary::Periodic);
+        assert_eq!(word & 0b111, 0b100);
+        assert_eq!(word >> 2, packed.word_at(0, Boundary::Periodic) & (!0 >> 2));
+        assert_eq!(packed.word_at(-2, Boundary::Fixed(1)) & 0b111, 0b111);
+        assert_eq!(packed.word_at(100, Boundary::Fixed(0)) >> 50, 0);
+    }
+    #[test]
+    fn packed_rule_matches_table() {
+        // Each window, as 64 copies, gives the rule output
+        for rule_nb in 0..256u64 {
+            let rule = Rule1D::new(2, rule_nb);
+            let packed = PackedRule::new(&rule).unwrap();
+            let mut values = vec![0; packed.nodes.len()];
+            for idx in 0..8 {
+                let vars = (0..3)
+                    .map(|i| if (idx >> (2 - i)) & 1 == 1 { !0 } else { 0 })
+                    .collect::<Vec<_>>();
+                let expected = if (rule_nb >> idx) & 1 == 1 { !0 } else { 0 };
+                assert_eq!(packed.apply(&vars, &mut values), expected);
+            }
+        }
+        // Rule 90 is left XOR right, a single variable is never needed twice
+        let packed = PackedRule::new(&Rule1D::new(2, 90)).unwrap();
+        assert_eq!(packed.nodes.len(), 2 + 3);
+        assert!(PackedRule::new(&Rule1D::new(3, 0)).is_none());
+        let rule = Rule1D::with_radius(2, 4, &BigUint::from(0u32));
+        assert!(PackedRule::new(&rule).is_none());
+    }
+}
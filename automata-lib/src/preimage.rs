@@ -0,0 +1,188 @@
+use crate::boundary::Boundary;
+use crate::cellular_rule::CellularRule;
+use crate::error::{Error, Result};
+use num_bigint::BigUint;
+use std::collections::HashMap;
+
+// Largest number of search states, the words of 4*radius cells
+const MAX_STATES: u64 = 1 << 16;
+
+// Cell of the window of a target cell
+#[derive(Clone, Copy)]
+enum Source {
+    // Preimage cell of this index
+    Cell(usize),
+    // Boundary value
+    Value(u8),
+}
+
+// Preimages of target cells, the cells that evolve to them in one step, in lexicographic order.
+// Preimage cells are chosen from left to right, and each target cell is checked once the last preimage cell
+// of its window is chosen. All the cells a check reads are among the first 2r and the last 2r+1 chosen cells,
+// these first and last 2r cells are the state of the search.
+// The preimages that complete each state are counted first, from the last cell backward,
+// so that the search only follows states that complete and the count of all preimages is known.
+pub(crate) struct Preimages<'a, R: CellularRule> {
+    rule: &'a R,
+    radius: usize,
+    target: Vec<u8>,
+    // Windows of the target cells checked when each preimage cell is chosen, as (target index, window)
+    checks: Vec<Vec<(usize, Vec<Source>)>>,
+    // Number of ways to complete each state of each number of chosen cells
+    completions: Vec<HashMap<Vec<u8>, BigUint>>,
+    // Search position: chosen cells, their states and the next color to try after each number of chosen cells
+    cells: Vec<u8>,
+    states: Vec<Vec<u8>>,
+    next_colors: Vec<u8>,
+}
+impl<'a, R: CellularRule> Preimages<'a, R> {
+    // Preimages of 'size' cells of 'target' on a lattice with 'boundary',
+    // the window of target cell i being centered on the preimage cell i+offset.
+    pub(crate) fn new(
+        rule: &'a R,
+        boundary: Boundary,
+        target: &[u8],
+        offset: i64,
+        size: usize,
+    ) -> Result<Preimages<'a, R>> {
+        let (n_colors, radius) = (rule.get_n_colors(), rule.get_radius());
+        let n_states = (n_colors as u64).checked_pow(4 * radius as u32);
+        if n_states.is_none_or(|n| n > MAX_STATES) {
+            return Err(Error::GraphTooLarge { n_colors, radius });
+        }
+        let fixed = match boundary {
+            Boundary::Fixed(value) => value,
+            _ => 0,
+        };
+        let r = radius as i64;
+        let mut checks = vec![Vec::new(); size];
+        for (i, _c) in target.iter().enumerate() {
+            let window = (-r..=r)
+                .map(|j| {
+                    boundary
+                        .index(size, i as i64 + offset + j)
+                        .map_or(Source::Value(fixed), Source::Cell)
+                })
+                .collect::<Vec<_>>();
+            let last = window
+                .iter()
+                .filter_map(|&source| match source {
+                    Source::Cell(idx) => Some(idx),
+                    Source::Value(_) => None,
+                })
+                .max()
+                .unwrap_or(0);
+            checks[last].push((i, window));
+        }
+        let mut search = Preimages {
+            rule,
+            radius: radius as usize,
+            target: target.to_vec(),
+            checks,
+            completions: Vec::new(),
+            cells: Vec::new(),
+            states: vec![Vec::new()],
+            next_colors: vec![0; size + 1],
+        };
+        search.completions = search.count_completions();
+        Ok(search)
+    }
+    pub(crate) fn n_preimages(&self) -> BigUint {
+        self.completions[0]
+            .get(&Vec::new())
+            .cloned()
+            .unwrap_or_default()
+    }
+    // States reached from the first cell forward, then their completions counted backward
+    fn count_completions(&self) -> Vec<HashMap<Vec<u8>, BigUint>> {
+        let size = self.checks.len();
+        let mut layers = vec![vec![Vec::new()]];
+        for j in 0..size {
+            let mut next = layers[j]
+                .iter()
+                .flat_map(|state| {
+                    (0..self.rule.get_n_colors()).filter_map(move |c| self.next_state(j, state, c))
+                })
+                .collect::<Vec<_>>();
+            next.sort_unstable();
+            next.dedup();
+            layers.push(next);
+        }
+        let mut completions = vec![HashMap::new(); size + 1];
+        for state in layers[size].drain(..) {
+            completions[size].insert(state, BigUint::from(1u32));
+        }
+        for j in (0..size).rev() {
+            for state in layers[j].drain(..) {
+                let count = (0..self.rule.get_n_colors())
+                    .filter_map(|c| self.next_state(j, &state, c))
+                    .filter_map(|next| completions[j + 1].get(&next))
+                    .sum::<BigUint>();
+                if count > BigUint::default() {
+                    completions[j].insert(state, count);
+                }
+            }
+        }
+        completions
+    }
+    // State after choosing the cell 'c' at index 'j' from 'state', None when a check fails
+    fn next_state(&self, j: usize, state: &[u8], c: u8) -> Option<Vec<u8>> {
+        let span = 2 * self.radius;
+        let n_first = j.min(span);
+        let (first, last) = state.split_at(n_first);
+        // Chosen cells from index 'start' to 'j'
+        let start = j.saturating_sub(span);
+        let recent = last.iter().copied().chain(Some(c)).collect::<Vec<_>>();
+        let cell = |source| match source {
+            Source::Cell(idx) if idx >= start => recent[idx - start],
+            Source::Cell(idx) => first[idx],
+            Source::Value(value) => value,
+        };
+        let mut window = Vec::with_capacity(span + 1);
+        for (i, sources) in self.checks[j].iter() {
+            window.clear();
+            window.extend(sources.iter().map(|&source| cell(source)));
+            if self.rule.apply(&window) != self.target[*i] {
+                return None;
+            }
+        }
+        let mut next = first.to_vec();
+        if n_first < span {
+            next.push(c);
+        }
+        next.extend_from_slice(&recent[recent.len() - (j + 1).min(span)..]);
+        Some(next)
+    }
+}
+// Depth first search, where each step either chooses the next cell or goes back when all colors were tried
+impl<'a, R: CellularRule> Iterator for Preimages<'a, R> {
+    type Item = Vec<u8>;
+    fn next(&mut self) -> Option<Vec<u8>> {
+        let size = self.checks.len();
+        loop {
+            let j = self.cells.len();
+            let c = self.next_colors[j];
+            if c == self.rule.get_n_colors() {
+                if j == 0 {
+                    return None;
+                }
+                self.next_colors[j] = 0;
+                self.cells.pop();
+                self.states.pop();
+                continue;
+            }
+            self.next_colors[j] += 1;
+            let next = match self.next_state(j, &self.states[j], c) {
+                Some(next) if self.completions[j + 1].contains_key(&next) => next,
+                _ => continue,
+            };
+            if j + 1 == size {
+                let mut preimage = self.cells.clone();
+                preimage.push(c);
+                return Some(preimage);
+            }
+            self.cells.push(c);
+            self.states.push(next);
+        }
+    }
+}
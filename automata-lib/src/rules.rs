@@ -1,90 +1,507 @@
-const BLACK: (u8, u8, u8) = (0u8, 0u8, 0u8);
-const BLUE: (u8, u8, u8) = (0u8, 0u8, 192u8);
-const CYAN: (u8, u8, u8) = (0u8, 192u8, 192u8);
-const WHITE: (u8, u8, u8) = (255u8, 255u8, 255u8);
+use num_bigint::BigUint;
+use num_traits::Zero;
+use std::fmt;
+use std::str::FromStr;
 
-// 2 Colors : All combinations, 8 entries, rule_nb < 2**8
-// 3 Colors : Ordered combinations, 10 entries (000,001,011,111,002,012,112,022,122,222) , rule_nb < 3**10
-// 4 Colors : Totalistics sum of cell values , 10 entries (0..9), rule_nb < 4**10
-#[derive(Debug)]
+use crate::cellular_rule::CellularRule;
+use crate::error::{Error, Result};
+
+/// Maximum number of colors supported by a rule.
+pub const MAX_COLORS: u8 = 16;
+// Maximum number of entries of a rule output table, bounds the full encoding.
+const MAX_ENTRIES: usize = 1 << 24;
+// Maximum size of the precomputed table indexed by the full window, used to speed up 'apply'.
+const MAX_LOOKUP: usize = 1 << 20;
+// Maximum number of outputs computed to enumerate the rules equivalent to a rule.
+const MAX_EQUIVALENT_ENTRIES: usize = 1 << 24;
+
+// Kind of encoding of the rule number, each digit in base 'n_colors' of the rule number
+// is the output for one entry of the encoding.
+// Rules use a neighborhood of 'radius' cells on each side of the updated cell, so a window of w=2r+1 cells.
+// Full : All combinations, entry is the window read as a base n_colors number, k**w entries
+// SymmetricOrdered : Ordered combinations, ordered by number of k-1, then number of k-2, ... then number of 1,
+//            for 3 colors radius 1: (000,001,011,111,002,012,112,022,122,222)
+// Totalistic : Sum of cell values, w*(k-1)+1 entries
+// OuterTotalistic : Sum of the neighbor cell values and value of the center cell, entry is k*sum+center,
+//            k*((w-1)*(k-1)+1) entries
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum RuleKind {
+    Full,
+    Totalistic,
+    OuterTotalistic,
+    SymmetricOrdered,
+}
+impl RuleKind {
+    pub const ALL: [RuleKind; 4] = [
+        RuleKind::Full,
+        RuleKind::Totalistic,
+        RuleKind::OuterTotalistic,
+        RuleKind::SymmetricOrdered,
+    ];
+    // Kind historically used for each number of colors:
+    // 2 Colors : Full, rule_nb < 2**8
+    // 3 Colors : SymmetricOrdered, rule_nb < 3**10
+    // 4+ Colors : Totalistic, for 4 colors rule_nb < 4**10
+    pub fn default_for(n_colors: u8) -> RuleKind {
+        match n_colors {
+            2 => RuleKind::Full,
+            3 => RuleKind::SymmetricOrdered,
+            _ => RuleKind::Totalistic,
+        }
+    }
+    // Return the inclusive maximum rule number of this kind for the provided number of colors
+    // and neighborhood radius.
+    pub fn max_nb(self, n_colors: u8, radius: u8) -> BigUint {
+        self.try_max_nb(n_colors, radius)
+            .unwrap_or_else(|e| panic!("{}", e))
+    }
+    pub fn try_max_nb(self, n_colors: u8, radius: u8) -> Result<BigUint> {
+        let n_entries = self.try_n_entries(n_colors, radius)?;
+        Ok(num_traits::pow(BigUint::from(n_colors), n_entries) - 1u32)
+    }
+    pub(crate) fn try_n_entries(self, n_colors: u8, radius: u8) -> Result<usize> {
+        if !(2..=MAX_COLORS).contains(&n_colors) {
+            return Err(Error::InvalidColors(n_colors));
+        }
+        if radius == 0 {
+            return Err(Error::InvalidRadius(radius));
+        }
+        self.n_entries(n_colors, radius).ok_or(Error::RuleTooLarge {
+            kind: self,
+            n_colors,
+            radius,
+        })
+    }
+    // Number of entries of the output table, None when it is larger than what is supported.
+    fn n_entries(self, n_colors: u8, radius: u8) -> Option<usize> {
+        let k = n_colors as usize;
+        let window = 2 * radius as usize + 1;
+        let n_entries = match self {
+            RuleKind::Full => k.checked_pow(window as u32)?,
+            RuleKind::SymmetricOrdered => binomial(window + k - 1, k - 1)?,
+            RuleKind::Totalistic => window * (k - 1) + 1,
+            RuleKind::OuterTotalistic => k * ((window - 1) * (k - 1) + 1),
+        };
+        if n_entries > MAX_ENTRIES {
+            None
+        } else {
+            Some(n_entries)
+        }
+    }
+    pub(crate) fn index(self, n_colors: u8, cells: &[u8]) -> usize {
+        let k = n_colors as usize;
+        match self {
+            RuleKind::Full => full_index(n_colors, cells),
+            RuleKind::SymmetricOrdered => {
+                let mut counts = [0usize; MAX_COLORS as usize];
+                for &c in cells {
+                    counts[c as usize] += 1;
+                }
+                let mut remaining = cells.len();
+                let mut idx = 0;
+                for c in (1..k).rev() {
+                    for j in 0..counts[c] {
+                        // Number of combinations filling the remaining cells with colors lower than c
+                        idx += binomial(remaining - j + c - 1, c - 1).unwrap();
+                    }
+                    remaining -= counts[c];
+                }
+                idx
+            }
+            RuleKind::Totalistic => cells.iter().map(|&c| c as usize).sum::<usize>(),
+            RuleKind::OuterTotalistic => {
+                let center = cells[cells.len() / 2] as usize;
+                let sum = cells.iter().map(|&c| c as usize).sum::<usize>() - center;
+                k * sum + center
+            }
+        }
+    }
+    // A window of 2*radius+1 cells whose entry is 'idx', the inverse of 'index'
+    pub(crate) fn window(self, n_colors: u8, radius: u8, idx: usize) -> Vec<u8> {
+        let k = n_colors as usize;
+        let mut cells = vec![0u8; 2 * radius as usize + 1];
+        match self {
+            RuleKind::Full => {
+                let mut rem = idx;
+                for cell in cells.iter_mut().rev() {
+                    *cell = (rem % k) as u8;
+                    rem /= k;
+                }
+            }
+            RuleKind::SymmetricOrdered => {
+                // Same counting as 'index', the cells are sorted with the highest colors last
+                let mut rem = idx;
+                let mut remaining = cells.len();
+                for c in (1..k).rev() {
+                    let mut count = 0;
+                    loop {
+                        let n = binomial(remaining - count + c - 1, c - 1).unwrap();
+                        if count == remaining || rem < n {
+                            break;
+                        }
+                        rem -= n;
+                        count += 1;
+                    }
+                    for cell in cells[remaining - count..remaining].iter_mut() {
+                        *cell = c as u8;
+                    }
+                    remaining -= count;
+                }
+            }
+            RuleKind::Totalistic => fill_sum(&mut cells, idx, n_colors),
+            RuleKind::OuterTotalistic => {
+                let center = cells.len() / 2;
+                fill_sum(&mut cells[1..], idx / k, n_colors);
+                cells.swap(0, center);
+                cells[center] = (idx % k) as u8;
+            }
+        }
+        cells
+    }
+}
+impl fmt::Display for RuleKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match self {
+            RuleKind::Full => "full",
+            RuleKind::Totalistic => "totalistic",
+            RuleKind::OuterTotalistic => "outer-totalistic",
+            RuleKind::SymmetricOrdered => "symmetric-ordered",
+        })
+    }
+}
+impl FromStr for RuleKind {
+    type Err = String;
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        RuleKind::ALL
+            .iter()
+            .find(|kind| kind.to_string() == s)
+            .copied()
+            .ok_or_else(|| format!("Unknown rule kind '{}'", s))
+    }
+}
+#[inline]
+fn full_index(n_colors: u8, cells: &[u8]) -> usize {
+    cells
+        .iter()
+        .fold(0, |idx, &c| idx * n_colors as usize + c as usize)
+}
+// Cells summing to 'sum', filled with the highest color first
+fn fill_sum(cells: &mut [u8], sum: usize, n_colors: u8) {
+    let mut rem = sum;
+    for cell in cells.iter_mut() {
+        *cell = rem.min(n_colors as usize - 1) as u8;
+        rem -= *cell as usize;
+    }
+}
+fn binomial(n: usize, k: usize) -> Option<usize> {
+    let mut res = 1usize;
+    for i in 0..k {
+        res = res.checked_mul(n - i)? / (i + 1);
+    }
+    Some(res)
+}
+
+#[derive(Debug, Clone)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(try_from = "RuleTable")
+)]
 pub struct Rule1D {
     n_colors: u8,
+    radius: u8,
+    kind: RuleKind,
+    outputs: Vec<u8>,
+    // Output for each window, indexed by the full encoding, empty when too large
+    #[cfg_attr(feature = "serde", serde(skip))]
+    lookup: Vec<u8>,
+}
+// Serialized fields of Rule1D, checked and completed with the lookup table on deserialization
+#[cfg(feature = "serde")]
+#[derive(serde::Deserialize)]
+struct RuleTable {
+    n_colors: u8,
+    radius: u8,
+    kind: RuleKind,
     outputs: Vec<u8>,
 }
+#[cfg(feature = "serde")]
+impl std::convert::TryFrom<RuleTable> for Rule1D {
+    type Error = String;
+    fn try_from(table: RuleTable) -> std::result::Result<Rule1D, String> {
+        let n_entries = table
+            .kind
+            .try_n_entries(table.n_colors, table.radius)
+            .map_err(|e| e.to_string())?;
+        if table.outputs.len() != n_entries || table.outputs.iter().any(|&c| c >= table.n_colors) {
+            return Err(String::from(
+                "rule outputs do not match the rule colors and radius",
+            ));
+        }
+        let mut rule = Rule1D {
+            n_colors: table.n_colors,
+            radius: table.radius,
+            kind: table.kind,
+            outputs: table.outputs,
+            lookup: Vec::new(),
+        };
+        rule.lookup = rule.build_lookup();
+        Ok(rule)
+    }
+}
 impl Rule1D {
+    // Constructors panic on invalid parameters, the try_ variants return an Error instead.
     pub fn new(n_colors: u8, rule_nb: u64) -> Rule1D {
-        assert!(vec![2, 3, 4].contains(&n_colors));
-        let rule_nb_max = Rule1D::get_max_nb(n_colors);
-        if rule_nb > rule_nb_max {
-            panic!("The provide rule_nb {} is incompatible with {} colors. Maximum for this number of colors is {}"
-                ,rule_nb,n_colors,rule_nb_max);
+        Rule1D::try_new(n_colors, rule_nb).unwrap_or_else(|e| panic!("{}", e))
+    }
+    pub fn try_new(n_colors: u8, rule_nb: u64) -> Result<Rule1D> {
+        Rule1D::try_with_radius(n_colors, 1, &BigUint::from(rule_nb))
+    }
+    // Rule using the kind historically associated with the number of colors.
+    pub fn with_radius(n_colors: u8, radius: u8, rule_nb: &BigUint) -> Rule1D {
+        Rule1D::try_with_radius(n_colors, radius, rule_nb).unwrap_or_else(|e| panic!("{}", e))
+    }
+    pub fn try_with_radius(n_colors: u8, radius: u8, rule_nb: &BigUint) -> Result<Rule1D> {
+        Rule1D::try_with_kind(RuleKind::default_for(n_colors), n_colors, radius, rule_nb)
+    }
+    pub fn with_kind(kind: RuleKind, n_colors: u8, radius: u8, rule_nb: &BigUint) -> Rule1D {
+        Rule1D::try_with_kind(kind, n_colors, radius, rule_nb).unwrap_or_else(|e| panic!("{}", e))
+    }
+    pub fn try_with_kind(
+        kind: RuleKind,
+        n_colors: u8,
+        radius: u8,
+        rule_nb: &BigUint,
+    ) -> Result<Rule1D> {
+        let n_entries = kind.try_n_entries(n_colors, radius)?;
+        let rule_nb_max = kind.try_max_nb(n_colors, radius)?;
+        if *rule_nb > rule_nb_max {
+            return Err(Error::RuleOutOfRange {
+                rule_nb: rule_nb.clone(),
+                max: rule_nb_max,
+            });
         }
-        let mut outputs = Vec::new();
-        match n_colors {
-            2 => {
-                for i in 0..8 {
-                    outputs.push(((rule_nb >> i) % 2) as u8);
+        let mut outputs = rule_nb.to_radix_le(n_colors as u32);
+        if rule_nb.is_zero() {
+            outputs.clear();
+        }
+        outputs.resize(n_entries, 0);
+        let mut rule = Rule1D {
+            n_colors,
+            radius,
+            kind,
+            outputs,
+            lookup: Vec::new(),
+        };
+        rule.lookup = rule.build_lookup();
+        Ok(rule)
+    }
+    fn build_lookup(&self) -> Vec<u8> {
+        let window = 2 * self.radius as usize + 1;
+        match RuleKind::Full.n_entries(self.n_colors, self.radius) {
+            Some(n) if n <= MAX_LOOKUP => {
+                let mut cells = vec![0u8; window];
+                (0..n)
+                    .map(|idx| {
+                        let mut rem = idx;
+                        for cell in cells.iter_mut().rev() {
+                            *cell = (rem % self.n_colors as usize) as u8;
+                            rem /= self.n_colors as usize;
+                        }
+                        self.outputs[self.kind.index(self.n_colors, &cells)]
+                    })
+                    .collect()
+            }
+            _ => Vec::new(),
+        }
+    }
+    // Return the inclusive maximum rule number supported for the provided number of colors as 'n_colors'
+    // and neighborhood radius as 'radius', using the kind historically associated to the number of colors.
+    pub fn get_max_nb(n_colors: u8, radius: u8) -> BigUint {
+        RuleKind::default_for(n_colors).max_nb(n_colors, radius)
+    }
+    pub fn try_get_max_nb(n_colors: u8, radius: u8) -> Result<BigUint> {
+        RuleKind::default_for(n_colors).try_max_nb(n_colors, radius)
+    }
+    #[inline]
+    pub fn get_kind(&self) -> RuleKind {
+        self.kind
+    }
+    // Rule number of this rule, in its kind encoding
+    pub fn get_rule_nb(&self) -> BigUint {
+        BigUint::from_radix_le(&self.outputs, self.n_colors as u32).unwrap()
+    }
+}
+// Equivalent rules, whose evolutions are the same up to a mirror of the lattice and a renaming of the colors.
+// The mirror of a rule applies it to the reversed window, only full rules have a different mirror.
+// Full and symmetric-ordered rules can rename colors with any permutation, while totalistic and
+// outer-totalistic rules only keep their sums with the identity and the reversal c -> k-1-c.
+// For 2 colors rule 30 is equivalent to its mirror 86, its complement 135 and its mirrored complement 149.
+impl Rule1D {
+    pub fn mirrored(&self) -> Rule1D {
+        let identity = (0..self.n_colors).collect::<Vec<_>>();
+        self.with_outputs(self.transformed_outputs(true, &identity))
+    }
+    // Rule where color c is renamed perm[c]
+    pub fn color_permuted(&self, perm: &[u8]) -> Result<Rule1D> {
+        let mut seen = vec![false; self.n_colors as usize];
+        let is_permutation = perm.len() == seen.len()
+            && perm
+                .iter()
+                .all(|&c| c < self.n_colors && !std::mem::replace(&mut seen[c as usize], true));
+        // Totalistic sums are only kept by the identity and the reversal
+        let is_affine = perm.iter().enumerate().all(|(c, &p)| p as usize == c)
+            || perm
+                .iter()
+                .enumerate()
+                .all(|(c, &p)| p + c as u8 + 1 == self.n_colors);
+        let kept = match self.kind {
+            RuleKind::Full | RuleKind::SymmetricOrdered => true,
+            RuleKind::Totalistic | RuleKind::OuterTotalistic => is_affine,
+        };
+        if !is_permutation || !kept {
+            return Err(Error::InvalidPermutation(perm.to_vec()));
+        }
+        Ok(self.with_outputs(self.transformed_outputs(false, perm)))
+    }
+    // Rule where color c is renamed k-1-c, supported by all kinds
+    pub fn complemented(&self) -> Rule1D {
+        let reversal = (0..self.n_colors).rev().collect::<Vec<_>>();
+        self.with_outputs(self.transformed_outputs(false, &reversal))
+    }
+    // Rule numbers of all the equivalent rules, in increasing order
+    pub fn equivalent_rule_nbs(&self) -> Result<Vec<BigUint>> {
+        let mut rule_nbs = self
+            .equivalent_outputs()?
+            .map(|outputs| BigUint::from_radix_le(&outputs, self.n_colors as u32).unwrap())
+            .collect::<Vec<_>>();
+        rule_nbs.sort();
+        rule_nbs.dedup();
+        Ok(rule_nbs)
+    }
+    // Equivalent rule with the smallest rule number
+    pub fn canonical(&self) -> Result<Rule1D> {
+        // Outputs are compared from the most significant digit of the rule number
+        let outputs = self
+            .equivalent_outputs()?
+            .min_by(|a, b| a.iter().rev().cmp(b.iter().rev()))
+            .unwrap();
+        Ok(self.with_outputs(outputs))
+    }
+    pub fn is_canonical(&self) -> Result<bool> {
+        Ok(self
+            .equivalent_outputs()?
+            .all(|outputs| outputs.iter().rev().ge(self.outputs.iter().rev())))
+    }
+    fn with_outputs(&self, outputs: Vec<u8>) -> Rule1D {
+        let mut rule = Rule1D {
+            n_colors: self.n_colors,
+            radius: self.radius,
+            kind: self.kind,
+            outputs,
+            lookup: Vec::new(),
+        };
+        rule.lookup = rule.build_lookup();
+        rule
+    }
+    // Outputs of the rule mirrored if 'mirror' and with color c renamed perm[c], in the same kind
+    fn transformed_outputs(&self, mirror: bool, perm: &[u8]) -> Vec<u8> {
+        let mut inverse = vec![0u8; perm.len()];
+        for (c, &p) in perm.iter().enumerate() {
+            inverse[p as usize] = c as u8;
+        }
+        (0..self.outputs.len())
+            .map(|idx| {
+                let mut cells = self.kind.window(self.n_colors, self.radius, idx);
+                for cell in cells.iter_mut() {
+                    *cell = inverse[*cell as usize];
+                }
+                if mirror {
+                    cells.reverse();
                 }
+                perm[self.apply(&cells) as usize]
+            })
+            .collect()
+    }
+    // Color permutations that keep a rule in its kind
+    fn color_permutations_of_kind(&self) -> Box<dyn Iterator<Item = Vec<u8>>> {
+        let identity = (0..self.n_colors).collect::<Vec<_>>();
+        match self.kind {
+            RuleKind::Full | RuleKind::SymmetricOrdered => Box::new(Permutations(Some(identity))),
+            RuleKind::Totalistic | RuleKind::OuterTotalistic => {
+                let reversal = identity.iter().rev().copied().collect();
+                Box::new(vec![identity, reversal].into_iter())
             }
-            3 => {
-                outputs.push((rule_nb % 3) as u8); // 0 0 0
-                outputs.push(((rule_nb / 3) % 3) as u8); // 0 0 1
-                outputs.push(((rule_nb / 9) % 3) as u8); // 0 1 1
-                outputs.push(((rule_nb / 27) % 3) as u8); // 1 1 1
-                outputs.push(((rule_nb / 81) % 3) as u8); // 0 0 2
-                outputs.push(((rule_nb / 243) % 3) as u8); // 0 1 2
-                outputs.push(((rule_nb / 729) % 3) as u8); // 1 1 2
-                outputs.push(0);
-                outputs.push(((rule_nb / (3 * 729)) % 3) as u8); // 0 2 2
-                outputs.push(((rule_nb / (9 * 729)) % 3) as u8); // 1 2 2
-                outputs.push(0);
-                outputs.push(0);
-                outputs.push(((rule_nb / (27 * 729)) % 3) as u8); // 2 2 2
+        }
+    }
+    // Outputs of all the transformations of the rule, some of them can be identical
+    fn equivalent_outputs(&self) -> Result<impl Iterator<Item = Vec<u8>> + '_> {
+        let n_permutations = match self.kind {
+            RuleKind::Full | RuleKind::SymmetricOrdered => {
+                (2..=self.n_colors as usize).try_fold(1usize, |n, c| n.checked_mul(c))
             }
+            RuleKind::Totalistic | RuleKind::OuterTotalistic => Some(2),
+        };
+        let mirrors: &[bool] = if self.kind == RuleKind::Full {
+            &[false, true]
+        } else {
+            &[false]
+        };
+        match n_permutations.and_then(|n| n.checked_mul(mirrors.len() * self.outputs.len())) {
+            Some(n) if n <= MAX_EQUIVALENT_ENTRIES => {}
             _ => {
-                for i in 0..10 {
-                    outputs.push(((rule_nb >> (2 * i)) % 4) as u8);
-                }
+                return Err(Error::TooManyEquivalents {
+                    kind: self.kind,
+                    n_colors: self.n_colors,
+                    radius: self.radius,
+                })
             }
         }
-        Rule1D { n_colors, outputs }
+        Ok(self.color_permutations_of_kind().flat_map(move |perm| {
+            mirrors
+                .iter()
+                .map(move |&mirror| self.transformed_outputs(mirror, &perm))
+                .collect::<Vec<_>>()
+        }))
     }
-    // Return the inclusive maximum rule number supported for the provided number of colors as 'n_colors'
-    pub fn get_max_nb(n_colors: u8) -> u64 {
-        assert!(vec![2, 3, 4].contains(&n_colors));
-        match n_colors {
-            2 => 255,
-            3 => 59048,
-            _ => 1048575,
+}
+// All the permutations in lexicographic order, starting from the provided one
+struct Permutations(Option<Vec<u8>>);
+impl Iterator for Permutations {
+    type Item = Vec<u8>;
+    fn next(&mut self) -> Option<Vec<u8>> {
+        let perm = self.0.take()?;
+        // Next permutation: swap the last ascent with its smallest larger successor, then reverse the tail
+        if let Some(i) = (1..perm.len()).rev().find(|&i| perm[i - 1] < perm[i]) {
+            let mut next = perm.clone();
+            let j = (i..next.len())
+                .rev()
+                .find(|&j| next[j] > next[i - 1])
+                .unwrap();
+            next.swap(i - 1, j);
+            next[i..].reverse();
+            self.0 = Some(next);
         }
+        Some(perm)
     }
-    pub fn initialize(&self) -> Vec<u8> {
-        return vec![0, 0, 0, self.n_colors - 1, 0, 0, 0];
+}
+impl CellularRule for Rule1D {
+    #[inline]
+    fn get_n_colors(&self) -> u8 {
+        self.n_colors
     }
     #[inline]
-    pub fn apply(&self, cells: &[u8]) -> u8 {
-        assert_eq!(cells.len(), 3);
-        let idx = match self.n_colors {
-            2 => ((cells[0] as usize) << 2) + ((cells[1] as usize) << 1) + (cells[2] as usize),
-            3 => (cells[0] * cells[0] + cells[1] * cells[1] + cells[2] * cells[2]) as usize,
-            _ => cells.iter().sum::<u8>() as usize,
-        };
-        *self.outputs.get(idx).unwrap()
-    }
-    pub fn cell_to_text(&self, cell: &u8) -> String {
-        String::from(match *cell {
-            0 => " ",
-            x if x == self.n_colors - 1 => "*",
-            x if x == self.n_colors - 2 => "+",
-            _ => "-",
-        })
+    fn get_radius(&self) -> u8 {
+        self.radius
     }
-    pub fn cell_to_rgb(&self, cell: &u8) -> (u8, u8, u8) {
-        match *cell {
-            0 => WHITE,
-            x if x == self.n_colors - 1 => BLACK,
-            x if x == self.n_colors - 2 => BLUE,
-            _ => CYAN,
+    #[inline]
+    fn apply(&self, cells: &[u8]) -> u8 {
+        assert_eq!(cells.len(), 2 * self.radius as usize + 1);
+        if self.lookup.is_empty() {
+            self.outputs[self.kind.index(self.n_colors, cells)]
+        } else {
+            self.lookup[full_index(self.n_colors, cells)]
         }
     }
 }
@@ -96,21 +513,220 @@ mod tests {
     #[test]
     fn rule_works() {
         let rule = Rule1D::new(2, 0);
-        assert_eq!(rule.apply(&vec![1, 1, 0]), 0);
+        assert_eq!(rule.apply(&[1, 1, 0]), 0);
         let rule = Rule1D::new(2, 1);
-        assert_eq!(rule.apply(&vec![0, 0, 0]), 1);
-        assert_eq!(rule.apply(&vec![1, 0, 0]), 0);
-        assert_eq!(rule.apply(&vec![0, 1, 1]), 0);
+        assert_eq!(rule.apply(&[0, 0, 0]), 1);
+        assert_eq!(rule.apply(&[1, 0, 0]), 0);
+        assert_eq!(rule.apply(&[0, 1, 1]), 0);
         let rule = Rule1D::new(2, 2);
-        assert_eq!(rule.apply(&vec![0, 0, 0]), 0);
-        assert_eq!(rule.apply(&vec![0, 0, 1]), 1);
-        assert_eq!(rule.apply(&vec![0, 1, 0]), 0);
+        assert_eq!(rule.apply(&[0, 0, 0]), 0);
+        assert_eq!(rule.apply(&[0, 0, 1]), 1);
+        assert_eq!(rule.apply(&[0, 1, 0]), 0);
+        assert_eq!(rule.get_rule_nb(), BigUint::from(2u32));
+        let rule_nb = BigUint::from(123_456_789u32);
+        let rule = Rule1D::with_kind(RuleKind::OuterTotalistic, 5, 2, &rule_nb);
+        assert_eq!(rule.get_rule_nb(), rule_nb);
+        assert_eq!(Rule1D::new(3, 0).get_rule_nb(), BigUint::from(0u32));
     }
     #[test]
     fn rule2c_30_works() {
         let rule = Rule1D::new(2, 30);
-        assert_eq!(rule.apply(&vec![1, 1, 1]), 0);
-        assert_eq!(rule.apply(&vec![0, 1, 1]), 1);
-        assert_eq!(rule.apply(&vec![1, 1, 0]), 0);
+        assert_eq!(rule.apply(&[1, 1, 1]), 0);
+        assert_eq!(rule.apply(&[0, 1, 1]), 1);
+        assert_eq!(rule.apply(&[1, 1, 0]), 0);
+    }
+    #[test]
+    fn rule3c_matches_ordered_combinations() {
+        // Each digit of the rule number is the output of one ordered combination
+        let rule = Rule1D::new(3, 2 * 3u64.pow(7) + 3u64.pow(9));
+        assert_eq!(rule.apply(&[0, 2, 2]), 2);
+        assert_eq!(rule.apply(&[2, 0, 2]), 2);
+        assert_eq!(rule.apply(&[2, 2, 2]), 1);
+        assert_eq!(rule.apply(&[1, 2, 2]), 0);
+    }
+    #[test]
+    fn rule_radius_works() {
+        assert_eq!(Rule1D::get_max_nb(2, 2), BigUint::from(u32::MAX));
+        assert_eq!(
+            Rule1D::get_max_nb(2, 3),
+            (BigUint::from(1u32) << 128) - 1u32
+        );
+        assert_eq!(Rule1D::get_max_nb(4, 2), BigUint::from(4u64.pow(16) - 1));
+        // Output is 1 only for the 11111 neighborhood
+        let rule = Rule1D::with_radius(2, 2, &BigUint::from(1u64 << 31));
+        assert_eq!(rule.apply(&[1, 1, 1, 1, 1]), 1);
+        assert_eq!(rule.apply(&[1, 1, 1, 1, 0]), 0);
+        assert_eq!(rule.initialize(), vec![0, 0, 0, 0, 0, 1, 0, 0, 0, 0, 0]);
+    }
+    #[test]
+    fn rule_k_colors_works() {
+        // 5 colors totalistic, output is the sum modulo 5
+        let rule_nb = (0..13u32).fold(BigUint::from(0u32), |nb, sum| {
+            nb + BigUint::from(sum % 5) * num_traits::pow(BigUint::from(5u32), sum as usize)
+        });
+        let rule = Rule1D::with_kind(RuleKind::Totalistic, 5, 1, &rule_nb);
+        assert_eq!(rule.apply(&[4, 4, 3]), 1);
+        assert_eq!(rule.apply(&[1, 2, 1]), 4);
+        assert_eq!(Rule1D::get_max_nb(5, 1), rule_nb_max(5, 13));
+        // 16 colors full and outer totalistic rule spaces do not fit in a u64
+        assert_eq!(
+            Rule1D::with_kind(RuleKind::Full, 16, 1, &rule_nb_max(16, 4096)).apply(&[3, 8, 15]),
+            15
+        );
+        // Only neighbor sum 2 with a center 0 is mapped to 7
+        let rule_nb = BigUint::from(7u32) * num_traits::pow(BigUint::from(16u32), 16 * 2);
+        let rule = Rule1D::with_kind(RuleKind::OuterTotalistic, 16, 1, &rule_nb);
+        assert_eq!(rule.apply(&[0, 0, 0]), 0);
+        assert_eq!(rule.apply(&[0, 0, 1]), 0);
+        assert_eq!(rule.apply(&[0, 1, 0]), 0);
+        assert_eq!(rule.apply(&[1, 0, 1]), 7);
+        assert_eq!(rule.apply(&[1, 0, 1]), rule.apply(&[2, 0, 0]));
+    }
+    #[test]
+    fn palette_scales_with_colors() {
+        let rule = Rule1D::with_radius(8, 1, &BigUint::from(0u32));
+        let colors = (0..8).map(|c| rule.cell_to_rgb(&c)).collect::<Vec<_>>();
+        for (i, c) in colors.iter().enumerate() {
+            assert!(!colors[i + 1..].contains(c));
+        }
+        assert_eq!(rule.cell_to_text(&0), " ");
+        assert_eq!(rule.cell_to_text(&3), "3");
+        assert_eq!(rule.cell_to_text(&7), "*");
+    }
+    fn rule_nb_max(n_colors: u32, n_entries: usize) -> BigUint {
+        num_traits::pow(BigUint::from(n_colors), n_entries) - 1u32
+    }
+    #[test]
+    fn rule_kinds_combine_with_colors() {
+        // Full 3 colors rule space
+        assert_eq!(
+            RuleKind::Full.max_nb(3, 1),
+            num_traits::pow(BigUint::from(3u32), 27) - 1u32
+        );
+        // Totalistic 2 colors rule 6 is the elementary rule 126
+        let totalistic = Rule1D::with_kind(RuleKind::Totalistic, 2, 1, &BigUint::from(6u32));
+        let elementary = Rule1D::new(2, 126);
+        for idx in 0..8u8 {
+            let cells = [idx >> 2, (idx >> 1) & 1, idx & 1];
+            assert_eq!(totalistic.apply(&cells), elementary.apply(&cells));
+        }
+        // Symmetric ordered is the historical 3 colors encoding
+        let rule = Rule1D::with_kind(RuleKind::SymmetricOrdered, 3, 1, &BigUint::from(40327u32));
+        let legacy = Rule1D::new(3, 40327);
+        for idx in 0..27u8 {
+            let cells = [idx / 9, (idx / 3) % 3, idx % 3];
+            assert_eq!(rule.apply(&cells), legacy.apply(&cells));
+        }
+        assert_eq!(
+            RuleKind::SymmetricOrdered.max_nb(2, 1),
+            BigUint::from(15u32)
+        );
+        for kind in RuleKind::ALL.iter() {
+            assert_eq!(kind.to_string().parse::<RuleKind>(), Ok(*kind));
+        }
+    }
+    #[test]
+    fn rule_windows_match_entries() {
+        for kind in RuleKind::ALL.iter() {
+            for &(n_colors, radius) in &[(2, 1), (3, 1), (4, 2), (5, 1)] {
+                for idx in 0..kind.n_entries(n_colors, radius).unwrap() {
+                    let cells = kind.window(n_colors, radius, idx);
+                    assert_eq!(kind.index(n_colors, &cells), idx, "{} {:?}", kind, cells);
+                }
+            }
+        }
+    }
+    #[test]
+    fn rule_equivalences_work() {
+        let rule = Rule1D::new(2, 30);
+        assert_eq!(rule.mirrored().get_rule_nb(), BigUint::from(86u32));
+        assert_eq!(rule.complemented().get_rule_nb(), BigUint::from(135u32));
+        assert_eq!(
+            rule.color_permuted(&[1, 0])
+                .unwrap()
+                .mirrored()
+                .get_rule_nb(),
+            BigUint::from(149u32)
+        );
+        let class = [30u32, 86, 135, 149]
+            .iter()
+            .map(|&nb| BigUint::from(nb))
+            .collect::<Vec<_>>();
+        assert_eq!(rule.equivalent_rule_nbs().unwrap(), class);
+        let rule = Rule1D::new(2, 149);
+        assert!(!rule.is_canonical().unwrap());
+        assert_eq!(
+            rule.canonical().unwrap().get_rule_nb(),
+            BigUint::from(30u32)
+        );
+        assert!(Rule1D::new(2, 30).is_canonical().unwrap());
+        // There are 88 classes of elementary rules
+        let n_canonical = (0..256u64)
+            .filter(|&nb| Rule1D::new(2, nb).is_canonical().unwrap())
+            .count();
+        assert_eq!(n_canonical, 88);
+        // Transformed rules apply the rule to the transformed windows, for every kind
+        let rule_nb = BigUint::from(987_654_321u32);
+        for kind in RuleKind::ALL.iter() {
+            let rule = Rule1D::with_kind(*kind, 3, 1, &(&rule_nb % (kind.max_nb(3, 1) + 1u32)));
+            let perm = [2, 0, 1];
+            let permuted = rule.color_permuted(&perm);
+            let mirrored = rule.mirrored();
+            let complemented = rule.complemented();
+            for idx in 0..27u8 {
+                let cells = [idx / 9, (idx / 3) % 3, idx % 3];
+                let reversed = [cells[2], cells[1], cells[0]];
+                assert_eq!(mirrored.apply(&cells), rule.apply(&reversed));
+                let inverted = cells.iter().map(|&c| 2 - c).collect::<Vec<_>>();
+                assert_eq!(complemented.apply(&cells), 2 - rule.apply(&inverted));
+                if let Ok(permuted) = &permuted {
+                    let inverse = cells.iter().map(|&c| (c + 1) % 3).collect::<Vec<_>>();
+                    assert_eq!(permuted.apply(&cells), perm[rule.apply(&inverse) as usize]);
+                }
+            }
+            let canonical = rule.canonical().unwrap();
+            assert!(canonical.is_canonical().unwrap());
+            assert!(canonical.get_rule_nb() <= rule.get_rule_nb());
+            assert!(rule
+                .equivalent_rule_nbs()
+                .unwrap()
+                .contains(&canonical.get_rule_nb()));
+        }
+        // Totalistic sums are not kept by a rotation of the colors
+        let rule_nb = &rule_nb % (RuleKind::Totalistic.max_nb(3, 1) + 1u32);
+        let rule = Rule1D::with_kind(RuleKind::Totalistic, 3, 1, &rule_nb);
+        assert!(matches!(
+            rule.color_permuted(&[2, 0, 1]),
+            Err(Error::InvalidPermutation(_))
+        ));
+        assert!(rule.color_permuted(&[0, 0, 1]).is_err());
+        assert_eq!(rule.equivalent_rule_nbs().unwrap().len(), 2);
+        assert!(matches!(
+            Rule1D::with_kind(RuleKind::Full, 16, 1, &rule_nb).canonical(),
+            Err(Error::TooManyEquivalents { .. })
+        ));
+    }
+    #[test]
+    fn rule_errors() {
+        assert!(matches!(
+            Rule1D::try_new(17, 0),
+            Err(Error::InvalidColors(17))
+        ));
+        assert!(matches!(
+            Rule1D::try_with_radius(2, 0, &BigUint::from(0u32)),
+            Err(Error::InvalidRadius(0))
+        ));
+        assert!(matches!(
+            Rule1D::try_with_kind(RuleKind::Full, 16, 4, &BigUint::from(0u32)),
+            Err(Error::RuleTooLarge { .. })
+        ));
+        match Rule1D::try_new(2, 256) {
+            Err(e @ Error::RuleOutOfRange { .. }) => assert_eq!(
+                e.to_string(),
+                "Rule number 256 is out of range, maximum is 255"
+            ),
+            _ => panic!("Rule 256 must be out of range for 2 colors"),
+        }
+        assert!(Rule1D::try_get_max_nb(1, 1).is_err());
     }
 }
@@ -0,0 +1,266 @@
+use crate::automata::{apply_windows, step_finite_cells};
+use crate::boundary::Boundary;
+use crate::cellular_rule::CellularRule;
+use crate::error::{Error, Result};
+use crate::init::InitialCondition;
+use crate::rules::Rule1D;
+use crate::spacetime::SpaceTime;
+use image::RgbImage;
+
+// Second order automata, whose next cells depend on the current and on the previous cells:
+// next = rule(window) - previous, modulo the number of colors, an exclusive or with 2 colors.
+// Whatever the rule, the automata is reversible, previous = rule(window) - next is the same step
+// on the swapped generations.
+// Only finite lattices and deterministic rules are supported.
+#[derive(Clone)]
+pub struct SecondOrderAutomata1D<R: CellularRule = Rule1D> {
+    rule: R,
+    boundary: Boundary,
+    step: u32,
+    previous: Vec<u8>,
+    cells: Vec<u8>,
+    // Scratch buffers reused by each step
+    next: Vec<u8>,
+    padded: Vec<u8>,
+    view_start: i32,
+    view_width: u32,
+}
+impl<R: CellularRule> SecondOrderAutomata1D<R> {
+    // Automata on a finite lattice of 'size' cells, numbered from 0, starting from 'init'
+    // with previous cells of color 0.
+    pub fn with_init(
+        rule: R,
+        boundary: Boundary,
+        size: u32,
+        init: &InitialCondition,
+        view_start: i32,
+        view_width: u32,
+    ) -> Result<SecondOrderAutomata1D<R>> {
+        if size == 0 {
+            return Err(Error::InvalidSize(size));
+        }
+        let cells = init.cells(&rule, size)?;
+        let previous = vec![0; cells.len()];
+        SecondOrderAutomata1D::with_generations(
+            rule, boundary, previous, cells, view_start, view_width,
+        )
+    }
+    // Automata on a finite lattice from its 'previous' and current 'cells'
+    pub fn with_generations(
+        rule: R,
+        boundary: Boundary,
+        previous: Vec<u8>,
+        cells: Vec<u8>,
+        view_start: i32,
+        view_width: u32,
+    ) -> Result<SecondOrderAutomata1D<R>> {
+        let n_colors = rule.get_n_colors();
+        if rule.is_stochastic() {
+            return Err(Error::StochasticRule);
+        }
+        match boundary {
+            Boundary::Infinite => return Err(Error::UnsupportedBoundary(boundary)),
+            Boundary::Fixed(value) if value >= n_colors => {
+                return Err(Error::InvalidBoundaryValue(value))
+            }
+            _ if cells.is_empty() => return Err(Error::InvalidSize(0)),
+            _ => (),
+        }
+        if previous.len() != cells.len() {
+            return Err(Error::InvalidPattern(format!(
+                "{} previous cells for {} cells",
+                previous.len(),
+                cells.len()
+            )));
+        }
+        if let Some(&c) = previous.iter().chain(&cells).find(|&&c| c >= n_colors) {
+            return Err(Error::InvalidPattern(format!(
+                "{} is not a color of the rule",
+                c
+            )));
+        }
+        Ok(SecondOrderAutomata1D {
+            rule,
+            boundary,
+            step: 0,
+            previous,
+            cells,
+            next: Vec::new(),
+            padded: Vec::new(),
+            view_start,
+            view_width,
+        })
+    }
+    pub fn step(&mut self, n_step: u32) {
+        for _i in 0..n_step {
+            self.step_generation();
+        }
+        self.step += n_step;
+    }
+    // Go back 'n_step' steps, to the exact cells of that step
+    pub fn step_back(&mut self, n_step: u32) -> Result<()> {
+        if n_step > self.step {
+            return Err(Error::StepBeforeStart {
+                n_step,
+                current: self.step,
+            });
+        }
+        std::mem::swap(&mut self.previous, &mut self.cells);
+        for _i in 0..n_step {
+            self.step_generation();
+        }
+        std::mem::swap(&mut self.previous, &mut self.cells);
+        self.step -= n_step;
+        Ok(())
+    }
+    // The current cells become the previous cells, and the next cells the current cells
+    fn step_generation(&mut self) {
+        let n_colors = self.rule.get_n_colors();
+        self.next.clear();
+        self.next.extend_from_slice(&self.cells);
+        step_finite_cells(
+            &self.rule,
+            self.boundary,
+            &mut self.next,
+            &mut self.padded,
+            apply_windows::<R>,
+        );
+        for (c, &p) in self.next.iter_mut().zip(&self.previous) {
+            *c = (*c + n_colors - p) % n_colors;
+        }
+        std::mem::swap(&mut self.previous, &mut self.cells);
+        std::mem::swap(&mut self.cells, &mut self.next);
+    }
+    // Value of the cell at position 'x', as seen from the rule
+    pub fn get_cell(&self, x: i32) -> u8 {
+        self.boundary.get_cell(&self.cells, x as i64)
+    }
+    // Value of the cell at position 'x' on the previous step
+    pub fn get_previous_cell(&self, x: i32) -> u8 {
+        self.boundary.get_cell(&self.previous, x as i64)
+    }
+    // Cells of the view
+    pub fn iter(&self) -> impl Iterator<Item = u8> + '_ {
+        (self.view_start..self.view_start + self.view_width as i32).map(move |x| self.get_cell(x))
+    }
+    #[inline]
+    pub fn get_cur_step(&self) -> u32 {
+        self.step
+    }
+    #[inline]
+    pub fn get_view_width(&self) -> u32 {
+        self.view_width
+    }
+    #[inline]
+    pub fn get_rule(&self) -> &R {
+        &self.rule
+    }
+    #[inline]
+    pub fn get_boundary(&self) -> Boundary {
+        self.boundary
+    }
+    pub fn as_text(&self) -> String {
+        format!(
+            "|{}|",
+            self.iter()
+                .map(|c| self.rule.cell_to_text(&c))
+                .collect::<Vec<_>>()
+                .join("")
+        )
+    }
+    // Space-time diagram of the view, from the current step, then step 'n_step' times
+    pub fn space_time(&mut self, n_step: u32) -> SpaceTime {
+        let mut diagram = SpaceTime::new(self.view_start as i64, self.step as u64, self.view_width);
+        for _i in 0..n_step {
+            diagram.push_row(self.iter());
+            self.step(1)
+        }
+        diagram
+    }
+    pub fn as_image_buffer(&mut self, n_step: u32) -> RgbImage {
+        self.space_time(n_step).to_image(&self.rule)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use num_bigint::BigUint;
+
+    #[test]
+    fn second_order_works() {
+        // Rule 90 with 2 colors, the next cells are the exclusive or of the neighbors and of the previous cell
+        let mut automata = SecondOrderAutomata1D::with_generations(
+            Rule1D::new(2, 90),
+            Boundary::Fixed(0),
+            vec![0, 1, 1, 0, 0],
+            vec![0, 0, 1, 0, 0],
+            0,
+            5,
+        )
+        .unwrap();
+        automata.step(1);
+        assert_eq!(automata.cells, vec![0, 0, 1, 1, 0]);
+        assert_eq!(automata.previous, vec![0, 0, 1, 0, 0]);
+        assert_eq!(automata.as_text(), "|  ** |");
+        // N steps forward then N steps back recover the starting cells, for any rule and finite boundary
+        let rules = [30, 90, 110, 150]
+            .iter()
+            .map(|&nb| Rule1D::new(2, nb))
+            .chain(Some(Rule1D::new(3, 12345)))
+            .chain(Some(Rule1D::with_radius(
+                2,
+                2,
+                &BigUint::from(987654321u32),
+            )));
+        for rule in rules {
+            for &boundary in &[Boundary::Periodic, Boundary::Fixed(1), Boundary::Reflective] {
+                let init = InitialCondition::Random {
+                    density: 0.5,
+                    seed: 3,
+                };
+                let mut automata =
+                    SecondOrderAutomata1D::with_init(rule.clone(), boundary, 37, &init, 0, 37)
+                        .unwrap();
+                automata.step(2);
+                let (previous, cells) = (automata.previous.clone(), automata.cells.clone());
+                automata.step(100);
+                assert_eq!(automata.get_cur_step(), 102);
+                assert_ne!(automata.cells, cells);
+                automata.step_back(100).unwrap();
+                assert_eq!(automata.get_cur_step(), 2);
+                assert_eq!((automata.previous, automata.cells), (previous, cells));
+            }
+        }
+        let mut automata = SecondOrderAutomata1D::with_init(
+            Rule1D::new(2, 30),
+            Boundary::Periodic,
+            8,
+            &InitialCondition::Seed,
+            0,
+            8,
+        )
+        .unwrap();
+        automata.step(3);
+        assert!(automata.step_back(4).is_err());
+        assert_eq!(automata.space_time(4).height(), 4);
+        assert!(SecondOrderAutomata1D::with_generations(
+            Rule1D::new(2, 30),
+            Boundary::Infinite,
+            vec![0],
+            vec![1],
+            0,
+            1
+        )
+        .is_err());
+        assert!(SecondOrderAutomata1D::with_generations(
+            Rule1D::new(2, 30),
+            Boundary::Periodic,
+            vec![0, 0],
+            vec![1],
+            0,
+            1
+        )
+        .is_err());
+    }
+}
@@ -0,0 +1,211 @@
+use crate::cellular_rule::CellularRule;
+use image::RgbImage;
+
+// Space-time diagram: the cells of consecutive steps, on a window of consecutive positions.
+// Cells are indexed by their position 'x' and their step 't', as in the automata,
+// row t holds the cells of step t and column x the history of the cell at position x.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SpaceTime {
+    x_start: i64,
+    t_start: u64,
+    width: u32,
+    // Rows one after the other
+    cells: Vec<u8>,
+}
+impl SpaceTime {
+    // Empty diagram of 'width' positions from 'x_start', whose first row will be step 't_start'
+    pub fn new(x_start: i64, t_start: u64, width: u32) -> SpaceTime {
+        SpaceTime {
+            x_start,
+            t_start,
+            width,
+            cells: Vec::new(),
+        }
+    }
+    // Diagram made of the 'cells' rows, the number of cells must be a multiple of 'width'
+    pub fn from_cells(x_start: i64, t_start: u64, width: u32, cells: Vec<u8>) -> SpaceTime {
+        assert!(
+            cells.len().is_multiple_of(width as usize),
+            "{} cells cannot be split in rows of {} cells",
+            cells.len(),
+            width
+        );
+        SpaceTime {
+            x_start,
+            t_start,
+            width,
+            cells,
+        }
+    }
+    // Add the row of the step after the last one
+    pub fn push_row<I: IntoIterator<Item = u8>>(&mut self, row: I) {
+        let len = self.cells.len();
+        self.cells.extend(row);
+        assert_eq!(
+            self.cells.len() - len,
+            self.width as usize,
+            "Row does not have the diagram width"
+        );
+    }
+    // Add the rows of 'other', that must have the same positions and start after the last row
+    pub fn append(&mut self, other: &SpaceTime) {
+        assert!(
+            other.x_start == self.x_start
+                && other.width == self.width
+                && other.t_start == self.t_start + self.height() as u64,
+            "Diagrams do not follow each other"
+        );
+        self.cells.extend_from_slice(&other.cells);
+    }
+    // Forget the first 'n_rows' steps, to bound the size of a growing history
+    pub fn drop_first_rows(&mut self, n_rows: u32) {
+        let n_rows = n_rows.min(self.height());
+        self.cells.drain(..n_rows as usize * self.width as usize);
+        self.t_start += n_rows as u64;
+    }
+    #[inline]
+    pub fn x_start(&self) -> i64 {
+        self.x_start
+    }
+    #[inline]
+    pub fn t_start(&self) -> u64 {
+        self.t_start
+    }
+    #[inline]
+    pub fn width(&self) -> u32 {
+        self.width
+    }
+    // Number of steps
+    #[inline]
+    pub fn height(&self) -> u32 {
+        if self.width == 0 {
+            0
+        } else {
+            (self.cells.len() / self.width as usize) as u32
+        }
+    }
+    // Cell at position 'x' on step 't', None outside of the diagram
+    pub fn get(&self, x: i64, t: u64) -> Option<u8> {
+        let col = self.col_index(x)?;
+        self.row(t).map(|row| row[col])
+    }
+    // Cells of step 't'
+    pub fn row(&self, t: u64) -> Option<&[u8]> {
+        let row = t.checked_sub(self.t_start)?;
+        if row >= self.height() as u64 {
+            return None;
+        }
+        let start = row as usize * self.width as usize;
+        Some(&self.cells[start..start + self.width as usize])
+    }
+    // History of the cell at position 'x', from the first step
+    pub fn column(&self, x: i64) -> Option<Vec<u8>> {
+        let col = self.col_index(x)?;
+        Some(self.rows().map(|row| row[col]).collect())
+    }
+    pub fn rows(&self) -> impl Iterator<Item = &[u8]> + '_ {
+        // chunks panics on a 0 size, an empty diagram has no rows anyway
+        self.cells.chunks(self.width.max(1) as usize)
+    }
+    // All the cells, row after row
+    pub fn as_slice(&self) -> &[u8] {
+        &self.cells
+    }
+    pub fn into_cells(self) -> Vec<u8> {
+        self.cells
+    }
+    // Part of the diagram with 'width' positions from 'x' and 'height' steps from 't',
+    // clipped to the diagram
+    pub fn crop(&self, x: i64, t: u64, width: u32, height: u32) -> SpaceTime {
+        let x0 = x.max(self.x_start);
+        let x1 = (x + width as i64).min(self.x_start + self.width as i64);
+        let t0 = t.max(self.t_start);
+        let t1 = (t + height as u64).min(self.t_start + self.height() as u64);
+        if x1 <= x0 || t1 <= t0 {
+            return SpaceTime::new(x0, t0, 0);
+        }
+        let (c0, c1) = ((x0 - self.x_start) as usize, (x1 - self.x_start) as usize);
+        let mut cropped = SpaceTime::new(x0, t0, (x1 - x0) as u32);
+        for t in t0..t1 {
+            cropped.push_row(self.row(t).unwrap()[c0..c1].iter().copied());
+        }
+        cropped
+    }
+    // One pixel per cell, step t on line t-t_start
+    pub fn to_image<R: CellularRule>(&self, rule: &R) -> RgbImage {
+        let buf = self
+            .cells
+            .iter()
+            .flat_map(|c| {
+                let (r, g, b) = rule.cell_to_rgb(c);
+                vec![r, g, b]
+            })
+            .collect::<Vec<_>>();
+        RgbImage::from_raw(self.width, self.height(), buf).unwrap()
+    }
+    pub fn to_rgb_vec<R: CellularRule>(&self, rule: &R) -> Vec<(u8, u8, u8)> {
+        self.cells.iter().map(|c| rule.cell_to_rgb(c)).collect()
+    }
+    // One line per step, framed like the automata text view
+    pub fn to_text<R: CellularRule>(&self, rule: &R) -> String {
+        self.rows()
+            .map(|row| {
+                let line = row
+                    .iter()
+                    .map(|c| rule.cell_to_text(c))
+                    .collect::<Vec<_>>()
+                    .join("");
+                format!("|{}|\n", line)
+            })
+            .collect()
+    }
+    fn col_index(&self, x: i64) -> Option<usize> {
+        let col = x.checked_sub(self.x_start)?;
+        if col < 0 || col >= self.width as i64 {
+            None
+        } else {
+            Some(col as usize)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rules::Rule1D;
+
+    #[test]
+    fn space_time_works() {
+        // Positions -1..=1 on steps 5..=7
+        let mut diagram = SpaceTime::new(-1, 5, 3);
+        diagram.push_row(vec![0, 1, 0]);
+        diagram.push_row(vec![1, 1, 1]);
+        diagram.push_row(vec![1, 0, 0]);
+        assert_eq!(diagram.height(), 3);
+        assert_eq!(diagram.get(-1, 6), Some(1));
+        assert_eq!(diagram.get(1, 7), Some(0));
+        assert_eq!(diagram.get(2, 6), None);
+        assert_eq!(diagram.get(0, 4), None);
+        assert_eq!(diagram.row(6), Some(&[1, 1, 1][..]));
+        assert_eq!(diagram.column(0), Some(vec![1, 1, 0]));
+        assert_eq!(diagram.column(-2), None);
+        let rule = Rule1D::new(2, 0);
+        assert_eq!(diagram.to_text(&rule), "| * |\n|***|\n|*  |\n");
+        let image = diagram.to_image(&rule);
+        assert_eq!(image.dimensions(), (3, 3));
+        assert_eq!(image.get_pixel(1, 0).0, [0, 0, 0]);
+        assert_eq!(image.get_pixel(0, 0).0, [255, 255, 255]);
+        // Cropping is clipped to the diagram
+        let cropped = diagram.crop(0, 6, 5, 5);
+        assert_eq!((cropped.x_start(), cropped.t_start()), (0, 6));
+        assert_eq!(cropped.as_slice(), &[1, 1, 0, 0]);
+        assert_eq!(diagram.crop(5, 6, 2, 2).height(), 0);
+        let mut next = SpaceTime::new(-1, 8, 3);
+        next.push_row(vec![0, 0, 1]);
+        diagram.append(&next);
+        assert_eq!(diagram.column(1), Some(vec![0, 1, 0, 1]));
+        diagram.drop_first_rows(3);
+        assert_eq!((diagram.t_start(), diagram.height()), (8, 1));
+        assert_eq!(diagram.row(8), Some(&[0, 0, 1][..]));
+    }
+}
@@ -0,0 +1,301 @@
+use crate::cellular_rule::CellularRule;
+use crate::error::{Error, Result};
+use crate::rules::{Rule1D, RuleKind};
+
+// Largest difference between 1 and the sum of the probabilities of a distribution
+const SUM_TOLERANCE: f64 = 1e-9;
+
+// Rule whose new cell is drawn at random, each entry of the output table of its kind is the distribution
+// of the new colors, distributions[entry][color].
+// The automata draws the new cells with its own random generator, so that runs are reproducible from its seed.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(try_from = "DistributionTable")
+)]
+pub struct StochasticRule {
+    n_colors: u8,
+    radius: u8,
+    kind: RuleKind,
+    distributions: Vec<Vec<f64>>,
+}
+// Serialized fields of StochasticRule, checked like the rules of try_new on deserialization
+#[cfg(feature = "serde")]
+#[derive(serde::Deserialize)]
+struct DistributionTable {
+    n_colors: u8,
+    radius: u8,
+    kind: RuleKind,
+    distributions: Vec<Vec<f64>>,
+}
+#[cfg(feature = "serde")]
+impl std::convert::TryFrom<DistributionTable> for StochasticRule {
+    type Error = String;
+    fn try_from(table: DistributionTable) -> std::result::Result<StochasticRule, String> {
+        StochasticRule::try_new(
+            table.kind,
+            table.n_colors,
+            table.radius,
+            table.distributions,
+        )
+        .map_err(|e| e.to_string())
+    }
+}
+impl StochasticRule {
+    pub fn try_new(
+        kind: RuleKind,
+        n_colors: u8,
+        radius: u8,
+        distributions: Vec<Vec<f64>>,
+    ) -> Result<StochasticRule> {
+        let n_entries = kind.try_n_entries(n_colors, radius)?;
+        if distributions.len() != n_entries {
+            return Err(Error::InvalidDistribution(format!(
+                "{} distributions for the {} entries of the rule",
+                distributions.len(),
+                n_entries
+            )));
+        }
+        for distribution in distributions.iter() {
+            if distribution.len() != n_colors as usize {
+                return Err(Error::InvalidDistribution(format!(
+                    "{} probabilities for {} colors",
+                    distribution.len(),
+                    n_colors
+                )));
+            }
+            if let Some(p) = distribution.iter().find(|p| !(0.0..=1.0).contains(*p)) {
+                return Err(Error::InvalidDistribution(format!(
+                    "probability {} is not between 0 and 1",
+                    p
+                )));
+            }
+            let sum = distribution.iter().sum::<f64>();
+            if (sum - 1.0).abs() > SUM_TOLERANCE {
+                return Err(Error::InvalidDistribution(format!(
+                    "probabilities sum to {}",
+                    sum
+                )));
+            }
+        }
+        Ok(StochasticRule {
+            n_colors,
+            radius,
+            kind,
+            distributions,
+        })
+    }
+    // 'rule' whose new cell is replaced with probability 'noise' by another color, all equally likely,
+    // with 2 colors the new cell is flipped.
+    pub fn noisy(rule: &Rule1D, noise: f64) -> Result<StochasticRule> {
+        if !(0.0..=1.0).contains(&noise) {
+            return Err(Error::InvalidDistribution(format!(
+                "probability {} is not between 0 and 1",
+                noise
+            )));
+        }
+        let (kind, n_colors, radius) = (rule.get_kind(), rule.get_n_colors(), rule.get_radius());
+        let n_entries = kind.try_n_entries(n_colors, radius)?;
+        let other = noise / (n_colors - 1) as f64;
+        let distributions = (0..n_entries)
+            .map(|idx| {
+                let output = rule.apply(&kind.window(n_colors, radius, idx));
+                (0..n_colors)
+                    .map(|c| if c == output { 1.0 - noise } else { other })
+                    .collect()
+            })
+            .collect();
+        StochasticRule::try_new(kind, n_colors, radius, distributions)
+    }
+    // Domany-Kinzel automata, 2 colors whose new cell only depends on its 2 neighbors:
+    // it is 1 with probability 'p1' when one neighbor is 1, 'p2' when both are, and 0 when none is.
+    // p2 = p1 is directed site percolation, p2 = p1*(2-p1) directed bond percolation.
+    pub fn domany_kinzel(p1: f64, p2: f64) -> Result<StochasticRule> {
+        // Outer totalistic entries are 2*(sum of the neighbors)+center
+        let distributions = [0.0, 0.0, p1, p1, p2, p2]
+            .iter()
+            .map(|&p| vec![1.0 - p, p])
+            .collect();
+        StochasticRule::try_new(RuleKind::OuterTotalistic, 2, 1, distributions)
+    }
+    #[inline]
+    pub fn get_kind(&self) -> RuleKind {
+        self.kind
+    }
+    // Probability of each new color for the window 'cells'
+    pub fn distribution(&self, cells: &[u8]) -> &[f64] {
+        &self.distributions[self.kind.index(self.n_colors, cells)]
+    }
+}
+impl CellularRule for StochasticRule {
+    #[inline]
+    fn get_n_colors(&self) -> u8 {
+        self.n_colors
+    }
+    #[inline]
+    fn get_radius(&self) -> u8 {
+        self.radius
+    }
+    // Most likely new cell, the lowest color on ties
+    fn apply(&self, cells: &[u8]) -> u8 {
+        let distribution = self.distribution(cells);
+        (0..distribution.len()).fold(0, |best, c| {
+            if distribution[c] > distribution[best] {
+                c
+            } else {
+                best
+            }
+        }) as u8
+    }
+    fn is_stochastic(&self) -> bool {
+        true
+    }
+    #[inline]
+    fn apply_random(&self, cells: &[u8], random: f64) -> u8 {
+        let distribution = self.distribution(cells);
+        let mut sum = 0.0;
+        for (c, &p) in distribution.iter().enumerate() {
+            sum += p;
+            if random < sum {
+                return c as u8;
+            }
+        }
+        // Rounding of the sum, the last color that can be drawn
+        distribution.iter().rposition(|&p| p > 0.0).unwrap() as u8
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::boundary::Boundary;
+    use crate::init::InitialCondition;
+    use crate::Automata1D;
+
+    #[test]
+    fn stochastic_rule_works() {
+        let rule = StochasticRule::noisy(&Rule1D::new(2, 110), 0.25).unwrap();
+        assert_eq!(rule.distribution(&[1, 1, 0]), &[0.25, 0.75]);
+        assert_eq!(rule.apply(&[1, 1, 0]), 1);
+        assert_eq!(rule.apply_random(&[1, 1, 0], 0.1), 0);
+        assert_eq!(rule.apply_random(&[1, 1, 0], 0.9), 1);
+        assert_eq!(rule.linear_coefficients(), None);
+        let rule = StochasticRule::noisy(&Rule1D::new(3, 777), 0.5).unwrap();
+        assert_eq!(rule.distribution(&[0, 0, 0]), &[0.5, 0.25, 0.25]);
+        assert!(StochasticRule::noisy(&Rule1D::new(2, 110), 1.5).is_err());
+        assert!(StochasticRule::try_new(RuleKind::Full, 2, 1, vec![vec![0.5, 0.5]; 7]).is_err());
+        assert!(StochasticRule::try_new(RuleKind::Full, 2, 1, vec![vec![0.5, 0.6]; 8]).is_err());
+        // Domany-Kinzel with certain births is the deterministic rule 250
+        let init = InitialCondition::Random {
+            density: 0.3,
+            seed: 1,
+        };
+        let run = |rule| {
+            let mut automata =
+                Automata1D::with_init(rule, Boundary::Periodic, 100, &init, 0, 100).unwrap();
+            automata.as_vec(30)
+        };
+        let mut expected =
+            Automata1D::with_init(Rule1D::new(2, 250), Boundary::Periodic, 100, &init, 0, 100)
+                .unwrap();
+        assert_eq!(
+            run(StochasticRule::domany_kinzel(1.0, 1.0).unwrap()),
+            expected.as_vec(30)
+        );
+        // Without noise the rule is the deterministic rule
+        let mut expected =
+            Automata1D::with_boundary(Rule1D::new(2, 30), Boundary::Fixed(0), 80, 0, 80).unwrap();
+        let mut automata = Automata1D::with_boundary(
+            StochasticRule::noisy(&Rule1D::new(2, 30), 0.0).unwrap(),
+            Boundary::Fixed(0),
+            80,
+            0,
+            80,
+        )
+        .unwrap();
+        assert_eq!(automata.as_vec(40), expected.as_vec(40));
+        // An infinite lattice would draw the whole background from the draws of its edge cells
+        let rule = StochasticRule::noisy(&Rule1D::new(2, 30), 0.1).unwrap();
+        assert!(matches!(
+            Automata1D::try_new(rule.clone(), -40, 80),
+            Err(Error::UnsupportedBoundary(Boundary::Infinite))
+        ));
+        assert!(Automata1D::with_init(rule, Boundary::Infinite, 10, &init, -40, 80).is_err());
+    }
+    #[test]
+    fn stochastic_automata_is_reproducible() {
+        let rule = StochasticRule::noisy(&Rule1D::new(2, 204), 0.1).unwrap();
+        let automata = |seed| {
+            let mut automata = Automata1D::with_init(
+                rule.clone(),
+                Boundary::Periodic,
+                10000,
+                &InitialCondition::Pattern(vec![0]),
+                0,
+                10000,
+            )
+            .unwrap();
+            automata.set_seed(seed);
+            automata
+        };
+        let (mut a, mut b, mut c) = (automata(1), automata(1), automata(2));
+        a.step(5);
+        b.step(2);
+        b.step(3);
+        c.step(5);
+        assert_eq!(a.as_vec(1), b.as_vec(1));
+        assert_ne!(a.as_vec(1), c.as_vec(1));
+        // Rule 204 keeps the cells, the noise flips one cell in ten on each step
+        let mut automata = automata(3);
+        automata.step(1);
+        let density = automata.as_vec(1).iter().filter(|&&c| c == 1).count() as f64 / 10000.0;
+        assert!((density - 0.1).abs() < 0.02);
+        // Domany-Kinzel below the percolation threshold dies out, above it survives
+        let survives = |p| {
+            let rule = StochasticRule::domany_kinzel(p, p).unwrap();
+            let mut automata = Automata1D::with_init(
+                rule,
+                Boundary::Periodic,
+                400,
+                &InitialCondition::Random {
+                    density: 1.0,
+                    seed: 0,
+                },
+                0,
+                400,
+            )
+            .unwrap();
+            automata.step(1000);
+            automata.as_vec(1).contains(&1)
+        };
+        assert!(!survives(0.6));
+        assert!(survives(0.8));
+    }
+    #[cfg(feature = "serde")]
+    #[test]
+    fn stochastic_rule_checkpoint_works() {
+        let checkpoint = |rule: StochasticRule| {
+            let mut automata =
+                Automata1D::with_boundary(rule, Boundary::Periodic, 50, 0, 50).unwrap();
+            automata.step(10);
+            let mut checkpoint = Vec::new();
+            automata.save_checkpoint(&mut checkpoint).unwrap();
+            (automata, checkpoint)
+        };
+        let (mut automata, bytes) =
+            checkpoint(StochasticRule::noisy(&Rule1D::new(2, 110), 0.1).unwrap());
+        let mut resumed = Automata1D::<StochasticRule>::load_checkpoint(&bytes[..]).unwrap();
+        assert_eq!(resumed.as_vec(20), automata.as_vec(20));
+        // Distributions are checked on loading, like in try_new
+        for invalid in [vec![-0.5, 1.5], vec![f64::NAN, 1.0], vec![0.5, 0.6]].iter() {
+            let mut rule = StochasticRule::domany_kinzel(0.5, 0.5).unwrap();
+            rule.distributions[2] = invalid.clone();
+            let (_automata, bytes) = checkpoint(rule);
+            assert!(matches!(
+                Automata1D::<StochasticRule>::load_checkpoint(&bytes[..]),
+                Err(Error::InvalidCheckpoint(_))
+            ));
+        }
+    }
+}
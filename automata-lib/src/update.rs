@@ -0,0 +1,230 @@
+use crate::boundary::Boundary;
+use crate::cellular_rule::CellularRule;
+use crate::error::{Error, Result};
+use rand::Rng;
+use rand_pcg::Pcg32;
+use std::fmt;
+use std::str::FromStr;
+
+// Order in which the cells of a finite lattice are updated during a step, one sweep of the lattice.
+// Apart from the synchronous scheme, each updated cell sees the cells updated before it.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum UpdateScheme {
+    // All cells at once, from the cells of the previous step
+    #[default]
+    Synchronous,
+    // As many updates as cells, each of a cell drawn at random, some cells are updated more than once
+    RandomSequential,
+    // Each cell in turn, from left to right
+    Sweep,
+    // Each cell with probability 'alpha', all at once, the other cells keep their value
+    Alpha(f64),
+    // Blocks of 'size' consecutive cells in turn, from left to right, the cells of a block at once
+    BlockSequential(u32),
+}
+impl UpdateScheme {
+    // Whether the updated cells are drawn with the random generator of the automata
+    pub fn is_random(self) -> bool {
+        matches!(
+            self,
+            UpdateScheme::RandomSequential | UpdateScheme::Alpha(_)
+        )
+    }
+    pub(crate) fn check(self) -> Result<()> {
+        match self {
+            UpdateScheme::Alpha(alpha) if !(0.0..=1.0).contains(&alpha) => Err(
+                Error::InvalidUpdateScheme(format!("alpha {} is not between 0 and 1", alpha)),
+            ),
+            UpdateScheme::BlockSequential(0) => Err(Error::InvalidUpdateScheme(String::from(
+                "blocks need at least one cell",
+            ))),
+            _ => Ok(()),
+        }
+    }
+}
+impl fmt::Display for UpdateScheme {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            UpdateScheme::Synchronous => write!(f, "synchronous"),
+            UpdateScheme::RandomSequential => write!(f, "random-sequential"),
+            UpdateScheme::Sweep => write!(f, "sweep"),
+            UpdateScheme::Alpha(alpha) => write!(f, "alpha:{}", alpha),
+            UpdateScheme::BlockSequential(size) => write!(f, "block:{}", size),
+        }
+    }
+}
+// Parse "synchronous", "random-sequential", "sweep", "alpha:<alpha>" or "block:<size>"
+impl FromStr for UpdateScheme {
+    type Err = String;
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        let update = match s {
+            "synchronous" => UpdateScheme::Synchronous,
+            "random-sequential" => UpdateScheme::RandomSequential,
+            "sweep" => UpdateScheme::Sweep,
+            _ => {
+                let alpha = s.strip_prefix("alpha:").map(|alpha| alpha.parse::<f64>());
+                let block = s.strip_prefix("block:").map(|size| size.parse::<u32>());
+                match (alpha, block) {
+                    (Some(Ok(alpha)), _) => UpdateScheme::Alpha(alpha),
+                    (_, Some(Ok(size))) => UpdateScheme::BlockSequential(size),
+                    _ => return Err(format!("Unknown update scheme '{}'", s)),
+                }
+            }
+        };
+        update.check().map_err(|e| e.to_string())?;
+        Ok(update)
+    }
+}
+
+// One step of the cells of a finite lattice with an asynchronous 'update', 'next' is a scratch buffer.
+// Stochastic rules and random schemes draw from 'rng'.
+pub(crate) fn update_cells<R: CellularRule>(
+    rule: &R,
+    boundary: Boundary,
+    update: UpdateScheme,
+    cells: &mut [u8],
+    next: &mut Vec<u8>,
+    rng: &mut Pcg32,
+) {
+    let size = cells.len();
+    let radius = rule.get_radius() as i64;
+    let mut window = Vec::with_capacity(2 * radius as usize + 1);
+    let mut new_cell = |cells: &[u8], i: usize, rng: &mut Pcg32| {
+        window.clear();
+        window.extend((i as i64 - radius..=i as i64 + radius).map(|j| boundary.get_cell(cells, j)));
+        if rule.is_stochastic() {
+            rule.apply_random(&window, rng.gen())
+        } else {
+            rule.apply(&window)
+        }
+    };
+    match update {
+        UpdateScheme::Synchronous => update_cells(
+            rule,
+            boundary,
+            UpdateScheme::BlockSequential(size as u32),
+            cells,
+            next,
+            rng,
+        ),
+        UpdateScheme::RandomSequential => {
+            for _k in 0..size {
+                let i = rng.gen_range(0, size);
+                cells[i] = new_cell(cells, i, rng);
+            }
+        }
+        UpdateScheme::Sweep => {
+            for i in 0..size {
+                cells[i] = new_cell(cells, i, rng);
+            }
+        }
+        UpdateScheme::Alpha(alpha) => {
+            next.clear();
+            next.extend_from_slice(cells);
+            for (i, cell) in next.iter_mut().enumerate() {
+                if rng.gen::<f64>() < alpha {
+                    *cell = new_cell(cells, i, rng);
+                }
+            }
+            cells.copy_from_slice(next);
+        }
+        UpdateScheme::BlockSequential(block) => {
+            for start in (0..size).step_by(block as usize) {
+                let end = (start + block as usize).min(size);
+                next.clear();
+                next.extend((start..end).map(|i| new_cell(cells, i, rng)));
+                cells[start..end].copy_from_slice(next);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::init::InitialCondition;
+    use crate::rules::Rule1D;
+    use crate::Automata1D;
+
+    #[test]
+    fn update_scheme_parse() {
+        for update in [
+            UpdateScheme::Synchronous,
+            UpdateScheme::RandomSequential,
+            UpdateScheme::Sweep,
+            UpdateScheme::Alpha(0.25),
+            UpdateScheme::BlockSequential(3),
+        ]
+        .iter()
+        {
+            assert_eq!(update.to_string().parse::<UpdateScheme>(), Ok(*update));
+        }
+        assert!("alpha:1.5".parse::<UpdateScheme>().is_err());
+        assert!("block:0".parse::<UpdateScheme>().is_err());
+        assert!("block".parse::<UpdateScheme>().is_err());
+    }
+    #[test]
+    fn update_scheme_works() {
+        let automata = |nb, cells: &[u8], update| {
+            let init = InitialCondition::Pattern(cells.to_vec());
+            let size = cells.len() as u32;
+            let mut automata =
+                Automata1D::with_init(Rule1D::new(2, nb), Boundary::Periodic, size, &init, 0, size)
+                    .unwrap();
+            automata.set_update_scheme(update).unwrap();
+            automata
+        };
+        // Rule 240 copies the left neighbor: a synchronous shift, while a sweep copies the first new cell
+        let mut shift = automata(240, &[0, 1, 1, 0, 0], UpdateScheme::Synchronous);
+        let mut sweep = automata(240, &[0, 1, 1, 0, 0], UpdateScheme::Sweep);
+        assert_eq!(shift.as_vec(2), vec![0, 1, 1, 0, 0, 0, 0, 1, 1, 0]);
+        assert_eq!(sweep.as_vec(2), vec![0, 1, 1, 0, 0, 0, 0, 0, 0, 0]);
+        // Blocks of the lattice size are synchronous, blocks of one cell are a sweep,
+        // alpha 1 is synchronous and alpha 0 keeps the cells
+        let init = [1, 0, 1, 1, 0, 0, 0, 1, 0, 1, 1];
+        let run = |update| automata(110, &init, update).as_vec(20);
+        let synchronous = run(UpdateScheme::Synchronous);
+        assert_eq!(run(UpdateScheme::BlockSequential(11)), synchronous);
+        assert_eq!(run(UpdateScheme::BlockSequential(20)), synchronous);
+        assert_eq!(run(UpdateScheme::Alpha(1.0)), synchronous);
+        assert_eq!(
+            run(UpdateScheme::BlockSequential(1)),
+            run(UpdateScheme::Sweep)
+        );
+        assert_ne!(run(UpdateScheme::BlockSequential(3)), synchronous);
+        assert!(run(UpdateScheme::Alpha(0.0))
+            .chunks(11)
+            .all(|row| row == init));
+        // Rule 255 sets each updated cell: alpha updates a fraction alpha of the cells,
+        // a random sequential sweep misses each cell with probability (1-1/n)^n, about 1/e
+        let density = |update| {
+            let mut automata = automata(255, &[0; 10000], update);
+            automata.step(1);
+            automata.as_vec(1).iter().filter(|&&c| c == 1).count() as f64 / 10000.0
+        };
+        assert!((density(UpdateScheme::Alpha(0.3)) - 0.3).abs() < 0.02);
+        assert!((density(UpdateScheme::RandomSequential) - (1.0 - (-1.0f64).exp())).abs() < 0.02);
+        // Random schemes are reproducible from the seed of the automata
+        let mut a = automata(110, &init, UpdateScheme::RandomSequential);
+        let mut b = automata(110, &init, UpdateScheme::RandomSequential);
+        a.step(7);
+        b.step(3);
+        b.step(4);
+        assert_eq!(a.as_vec(1), b.as_vec(1));
+        // Deterministic schemes have cycles, random schemes do not,
+        // and a sweep starting from the first cell has no traveling cycles
+        let cycle = automata(90, &init, UpdateScheme::Sweep)
+            .find_cycle(10000)
+            .unwrap();
+        assert_eq!(cycle.map(|cycle| cycle.shift), Some(0));
+        assert!(automata(90, &init, UpdateScheme::Alpha(0.5))
+            .find_cycle(100)
+            .is_err());
+        let mut automata = Automata1D::new(Rule1D::new(2, 30), -5, 10);
+        assert!(automata.set_update_scheme(UpdateScheme::Sweep).is_err());
+        assert!(automata
+            .set_update_scheme(UpdateScheme::Synchronous)
+            .is_ok());
+    }
+}
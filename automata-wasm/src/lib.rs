@@ -1,6 +1,6 @@
 mod utils;
 
-use automata_lib::{Automata1D, Rule1D};
+use automata_lib::{Automata1D, BigUint, Boundary, CellularRule, Rule1D, RuleKind};
 use wasm_bindgen::prelude::*;
 
 // When the `wee_alloc` feature is enabled, use `wee_alloc` as the global
@@ -23,14 +23,55 @@ pub struct WasmAutomata1D {
 }
 #[wasm_bindgen]
 impl WasmAutomata1D {
-    pub fn new(n_colors: u8, rule_nb: u64, width: u32) -> WasmAutomata1D {
+    // Rule numbers can exceed 64 bits, they are exchanged as decimal strings.
+    // Kind is the rule kind name, like "totalistic".
+    // Boundary is the boundary name, like "periodic", finite lattices are 'width' cells wide.
+    // Invalid parameters are reported as a javascript exception.
+    pub fn new(
+        n_colors: u8,
+        radius: u8,
+        kind: &str,
+        rule_nb: &str,
+        boundary: &str,
+        width: u32,
+    ) -> Result<WasmAutomata1D, JsValue> {
         utils::set_panic_hook();
-        let rule = Rule1D::new(n_colors, rule_nb);
-        let automata = Automata1D::new(rule, -(width as i32 / 2), width);
-        WasmAutomata1D { automata, width }
+        let kind = kind.parse::<RuleKind>().map_err(JsValue::from)?;
+        let rule_nb = rule_nb
+            .parse::<BigUint>()
+            .map_err(|e| JsValue::from(e.to_string()))?;
+        let rule = Rule1D::try_with_kind(kind, n_colors, radius, &rule_nb)
+            .map_err(|e| JsValue::from(e.to_string()))?;
+        let boundary = boundary.parse::<Boundary>().map_err(JsValue::from)?;
+        let view_start = if boundary.is_finite() {
+            0
+        } else {
+            -(width as i32 / 2)
+        };
+        let automata = Automata1D::with_boundary(rule, boundary, width, view_start, width)
+            .map_err(|e| JsValue::from(e.to_string()))?;
+        Ok(WasmAutomata1D { automata, width })
     }
-    pub fn get_max_rule_nb(n_colors: u8) -> u64 {
-        Rule1D::get_max_nb(n_colors)
+    // Automata resumed from a checkpoint returned by 'save'
+    pub fn load(checkpoint: &[u8]) -> Result<WasmAutomata1D, JsValue> {
+        utils::set_panic_hook();
+        let automata: Automata1D =
+            Automata1D::load_checkpoint(checkpoint).map_err(|e| JsValue::from(e.to_string()))?;
+        let width = automata.get_view_width();
+        Ok(WasmAutomata1D { automata, width })
+    }
+    pub fn save(&self) -> Result<Vec<u8>, JsValue> {
+        let mut checkpoint = Vec::new();
+        self.automata
+            .save_checkpoint(&mut checkpoint)
+            .map_err(|e| JsValue::from(e.to_string()))?;
+        Ok(checkpoint)
+    }
+    pub fn get_max_rule_nb(n_colors: u8, radius: u8, kind: &str) -> Result<String, JsValue> {
+        let kind = kind.parse::<RuleKind>().map_err(JsValue::from)?;
+        kind.try_max_nb(n_colors, radius)
+            .map(|max| max.to_string())
+            .map_err(|e| JsValue::from(e.to_string()))
     }
     pub fn step(&mut self, n_steps: u32) -> Vec<u8> {
         self.automata
@@ -42,4 +83,23 @@ impl WasmAutomata1D {
     pub fn width(&self) -> u32 {
         self.width
     }
+    // Parameters of the automata, in the format of 'new', to update the page after a load
+    pub fn n_colors(&self) -> u8 {
+        self.automata.get_rule().get_n_colors()
+    }
+    pub fn radius(&self) -> u8 {
+        self.automata.get_rule().get_radius()
+    }
+    pub fn kind(&self) -> String {
+        self.automata.get_rule().get_kind().to_string()
+    }
+    pub fn rule_nb(&self) -> String {
+        self.automata.get_rule().get_rule_nb().to_string()
+    }
+    pub fn boundary(&self) -> String {
+        self.automata.get_boundary().to_string()
+    }
+    pub fn cur_step(&self) -> u32 {
+        self.automata.get_cur_step()
+    }
 }
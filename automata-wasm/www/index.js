@@ -6,29 +6,49 @@ const OUTLINE_COLOR = "#888888";
 
 const canvas = document.getElementById("automata_canvas");
 const nb_colors_select = document.getElementById("nbColors")
+const radius_select = document.getElementById("radius")
+const kind_select = document.getElementById("kind")
 const rule_nb_input = document.getElementById("ruleNb")
 const random_button = document.getElementById("random")
+const boundary_select = document.getElementById("boundary")
 const playpause_button = document.getElementById("play-pause")
 const width_input = document.getElementById("width")
 const height_input = document.getElementById("height")
 const steps_label = document.getElementById("steps")
+const error_label = document.getElementById("error")
+const save_session_button = document.getElementById("save-session")
+const load_session_button = document.getElementById("load-session")
+const session_file_input = document.getElementById("session-file")
 const ctx = canvas.getContext("2d");
 
 let cur_row = 0;
 let width = 1024;
 let height = 512;
 let nColors = 3;
+let radius = 1;
+let kind = "symmetric-ordered";
 let ruleNb = BigInt(40327);
+let boundary = "infinite";
 let steps = 0;
 let need_reset = false;
 
 let automata = null;
 
 const reset_automata = () => {
-    automata = WasmAutomata1D.new(nColors, ruleNb, width);
-    cur_row = 0;
+    try {
+        automata = WasmAutomata1D.new(nColors, radius, kind, ruleNb.toString(), boundary, width);
+        error_label.textContent = "";
+    } catch (error) {
+        automata = null;
+        error_label.textContent = error;
+    }
     steps = 0;
     need_reset = false;
+    reset_canvas();
+}
+
+const reset_canvas = () => {
+    cur_row = 0;
     canvas.height = height + 2;
     canvas.width = width + 2;
     console.log("Width: %d , Height: %d", width, height);
@@ -40,7 +60,6 @@ const reset_automata = () => {
     ctx.moveTo(width + 2, height + 2); ctx.lineTo(0, height + 2);
     ctx.moveTo(width + 2, height + 2); ctx.lineTo(width + 2, 0);
     ctx.stroke();
-
 }
 
 const scroll_canvas = () => {
@@ -62,9 +81,14 @@ const draw_steps = () => {
     update_steps();
 }
 const update_rule_nb = () => {
-    var max_nb = WasmAutomata1D.get_max_rule_nb(nColors);
-    if (ruleNb > max_nb) {
-        ruleNb = max_nb;
+    try {
+        var max_nb = BigInt(WasmAutomata1D.get_max_rule_nb(nColors, radius, kind));
+        if (ruleNb > max_nb) {
+            ruleNb = max_nb;
+        }
+        error_label.textContent = "";
+    } catch (error) {
+        error_label.textContent = error;
     }
     rule_nb_input.value = ruleNb.toString();
     need_reset = true;
@@ -79,11 +103,22 @@ const update_height = () => {
     height_input.value = height;
     need_reset = true;
 }
+// Random BigInt in [0, bound[, drawn from more random digits than bound has
+const random_bigint = (bound) => {
+    let digits = "";
+    for (let i = 0; i < bound.toString().length + 8; i++) {
+        digits += Math.floor(Math.random() * 10).toString();
+    }
+    return BigInt(digits) % bound;
+}
 let animation_id = null;
 const play = () => {
     if (need_reset) {
         reset_automata();
     }
+    if (automata == null) {
+        return;
+    }
     playpause_button.textContent = "pause";
     render_loop();
 }
@@ -103,6 +138,18 @@ nb_colors_select.addEventListener("change", event => {
     nColors = nb_colors_select.value;
     update_rule_nb();
 })
+radius_select.addEventListener("change", event => {
+    radius = radius_select.value;
+    update_rule_nb();
+})
+kind_select.addEventListener("change", event => {
+    kind = kind_select.value;
+    update_rule_nb();
+})
+boundary_select.addEventListener("change", event => {
+    boundary = boundary_select.value;
+    need_reset = true;
+})
 rule_nb_input.addEventListener("input", event => {
     ruleNb = BigInt(rule_nb_input.value);
     update_rule_nb();
@@ -121,15 +168,73 @@ playpause_button.addEventListener("click", event => {
     }
 })
 random_button.addEventListener("click", event => {
-    var max_nb = WasmAutomata1D.get_max_rule_nb(nColors);
-    console.assert(max_nb + 1n < BigInt(Number.MAX_SAFE_INTEGER));
-    ruleNb = BigInt(Math.floor(Math.random() * Math.floor(Number(max_nb) + 1)));
+    try {
+        var max_nb = BigInt(WasmAutomata1D.get_max_rule_nb(nColors, radius, kind));
+        ruleNb = random_bigint(max_nb + 1n);
+    } catch (error) {
+        error_label.textContent = error;
+    }
     update_rule_nb()
 })
+// The session is the automata state, saved as a checkpoint file
+save_session_button.addEventListener("click", event => {
+    if (automata == null) {
+        return;
+    }
+    try {
+        const blob = new Blob([automata.save()], { type: "application/octet-stream" });
+        const link = document.createElement("a");
+        link.href = URL.createObjectURL(blob);
+        link.download = nColors + "C_" + ruleNb + ".ca";
+        link.click();
+        URL.revokeObjectURL(link.href);
+    } catch (error) {
+        error_label.textContent = error;
+    }
+})
+load_session_button.addEventListener("click", event => {
+    session_file_input.click();
+})
+session_file_input.addEventListener("change", event => {
+    const file = session_file_input.files[0];
+    session_file_input.value = "";
+    if (!file) {
+        return;
+    }
+    file.arrayBuffer().then(buffer => {
+        try {
+            automata = WasmAutomata1D.load(new Uint8Array(buffer));
+            error_label.textContent = "";
+        } catch (error) {
+            error_label.textContent = error;
+            return;
+        }
+        pause();
+        nColors = automata.n_colors();
+        radius = automata.radius();
+        kind = automata.kind();
+        ruleNb = BigInt(automata.rule_nb());
+        boundary = automata.boundary();
+        width = automata.width();
+        steps = automata.cur_step();
+        nb_colors_select.value = nColors;
+        radius_select.value = radius;
+        kind_select.value = kind;
+        rule_nb_input.value = ruleNb.toString();
+        boundary_select.value = boundary;
+        width_input.value = width;
+        need_reset = false;
+        reset_canvas();
+        update_steps();
+    });
+})
 
 width_input.value = width;
 height_input.value = height;
 nb_colors_select.value = nColors;
+radius_select.value = radius;
+kind_select.value = kind;
+boundary_select.value = boundary;
 playpause_button.textContent = "play";
 random_button.textContent = "random";
 